@@ -40,7 +40,13 @@ use crate::state::{
     ARCHIVED_PURCHASES,
     CONFIG_KEY,
 };
-use crate::tier::{ get_min_tier, get_tier, get_tier_from_nft_contract };
+use crate::tier::{
+    get_min_tier,
+    get_tier,
+    get_tier_at,
+    get_tier_from_nft_contract,
+    get_tier_held,
+};
 use crate::utils::{ self, assert_admin, assert_contract_active, assert_ido_admin };
 use cosmwasm_std::StdError;
 
@@ -105,6 +111,7 @@ pub fn execute(
             tokens_per_tier,
             payment,
             boycott_disabled,
+            min_tier_age,
             ..
         } => {
             let mut ido = Ido::default();
@@ -130,6 +137,7 @@ pub fn execute(
             }
 
             ido.boycott_disabled = boycott_disabled;
+            ido.min_tier_age = min_tier_age;
 
             start_ido(deps, env, info, ido)
         }
@@ -254,6 +262,19 @@ fn start_ido(
     Ok(Response::new().set_data(answer).add_submessage(sub_msg))
 }
 
+/// Tier of `address` in the given IDO. Tiers are taken as they were at the sale start, so a
+/// tier bought during the sale does not count. With `min_tier_age` the worst tier held during
+/// the `min_tier_age` before the start is used, so the tier has to be held all that time.
+fn ido_tier(deps: &Deps, ido: &Ido, address: String) -> StdResult<u8> {
+    match ido.min_tier_age {
+        Some(min_tier_age) => {
+            let window_start = ido.start_time.saturating_sub(min_tier_age);
+            get_tier_held(deps, address, window_start, ido.start_time)
+        }
+        None => get_tier_at(deps, address, ido.start_time),
+    }
+}
+
 fn buy_tokens(
     deps: DepsMut,
     env: Env,
@@ -285,7 +306,16 @@ fn buy_tokens(
     }
 
     let config = Config::load(deps.storage)?;
-    let tier = get_tier(&deps.as_ref(), sender.clone())?;
+    let mut user_ido_info = IDO_TO_INFO.may_load(deps.storage, (
+        canonical_sender.to_string(),
+        ido_id,
+    ))?.unwrap_or_default();
+
+    // The tier is fixed by the first purchase, so later purchases and a boycott use the same one
+    if user_ido_info.tier == 0 {
+        user_ido_info.tier = ido_tier(&deps.as_ref(), &ido, sender.clone())?;
+    }
+    let tier = user_ido_info.tier;
 
     let remaining_amount = ido.remaining_tokens_per_tier(tier);
     if remaining_amount == 0 {
@@ -321,11 +351,6 @@ fn buy_tokens(
     purchases.push(purchase);
     PURCHASES.save(deps.storage, (canonical_sender.to_string(), ido_id), &purchases)?;
 
-    let mut user_ido_info = IDO_TO_INFO.may_load(deps.storage, (
-        canonical_sender.to_string(),
-        ido_id,
-    ))?.unwrap_or_default();
-
     if user_ido_info.total_payment == 0 {
         ido.participants = ido.participants.checked_add(1).unwrap();
     }
@@ -663,6 +688,10 @@ pub fn boycott_ido(
         ido_id,
     ))?.unwrap_or_default();
 
+    if user_ido_info.total_tokens_bought == 0 {
+        return Err(ContractError::Std(StdError::generic_err("There are no purchases to boycott")));
+    }
+
     // Withdraw received tokens
     let withdraw_token_amount: Uint128 = user_ido_info.total_tokens_received.into();
     if user_ido_info.total_tokens_received != 0 {
@@ -717,7 +746,11 @@ pub fn boycott_ido(
         .checked_sub(user_ido_info.total_tokens_bought)
         .unwrap_or_default();
     ido.total_payment = ido.total_payment.checked_sub(refund_payment).unwrap_or_default();
-    let tier = get_tier(&deps.as_ref(), info.sender.to_string())?;
+    // Purchases made before the tier was stored fall back to evaluating it again
+    let tier = match user_ido_info.tier {
+        0 => ido_tier(&deps.as_ref(), &ido, info.sender.to_string())?,
+        tier => tier,
+    };
     let tier_index = tier.checked_sub(1).unwrap() as usize;
     ido.remaining_tokens_per_tier[tier_index] = ido.remaining_tokens_per_tier[tier_index]
         .checked_add(user_ido_info.total_tokens_bought)
@@ -726,6 +759,7 @@ pub fn boycott_ido(
     user_ido_info.total_tokens_received = 0;
     user_ido_info.total_tokens_bought = 0;
     user_ido_info.total_payment = 0;
+    user_ido_info.tier = 0;
 
     ido.save(deps.storage)?;
 
@@ -0,0 +1,246 @@
+use std::sync::{ Mutex, MutexGuard };
+
+use cosmwasm_std::{
+    coins,
+    Addr,
+    Binary,
+    Deps,
+    DepsMut,
+    Empty,
+    Env,
+    MessageInfo,
+    Response,
+    StdResult,
+    Timestamp,
+    Uint128,
+};
+use cw20::Cw20ExecuteMsg;
+use cw_multi_test::{ App, AppResponse, Contract, ContractWrapper, Executor };
+
+use crate::contract::{ execute, instantiate, query, ORAI };
+use crate::msg::{ ExecuteMsg, InitMsg, PaymentMethod, QueryMsg, QueryResponse };
+use crate::tier::manual::{ set_tier, set_tier_history };
+
+const ADMIN: &str = "admin";
+const ALICE: &str = "alice";
+const DAY: u64 = 24 * 60 * 60;
+
+/// The tier mock is global, tests that change it must not run at the same time.
+static TIER_MOCK: Mutex<()> = Mutex::new(());
+
+fn lock_tier_mock() -> MutexGuard<'static, ()> {
+    TIER_MOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn ido_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
+
+/// Token that accepts every transfer, the sale tokens are not what these tests look at.
+fn token_contract() -> Box<dyn Contract<Empty>> {
+    fn execute(_: DepsMut, _: Env, _: MessageInfo, _: Cw20ExecuteMsg) -> StdResult<Response> {
+        Ok(Response::new())
+    }
+    fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+        Ok(Response::new())
+    }
+    fn query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+        Ok(Binary::default())
+    }
+
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
+
+struct Sale {
+    app: App,
+    contract: Addr,
+    token: Addr,
+}
+
+/// Tiers 1 to 4 can buy 400, 300, 200 and 100 tokens for 1 ORAI each.
+fn setup() -> Sale {
+    let mut app = App::new(|router, _, storage| {
+        router.bank
+            .init_balance(storage, &Addr::unchecked(ALICE), coins(10_000, ORAI))
+            .unwrap();
+    });
+
+    let token_code_id = app.store_code(token_contract());
+    let token = app
+        .instantiate_contract(token_code_id, Addr::unchecked(ADMIN), &Empty {}, &[], "token", None)
+        .unwrap();
+
+    let code_id = app.store_code(ido_contract());
+    let msg = InitMsg {
+        admin: Some(ADMIN.to_string()),
+        lock_periods: vec![0, 0, 0, 0],
+        tier_contract: "tier".to_string(),
+        nft_contract: "nft".to_string(),
+    };
+    let contract = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "ido", None)
+        .unwrap();
+
+    Sale { app, contract, token }
+}
+
+impl Sale {
+    fn now(&self) -> u64 {
+        self.app.block_info().time.seconds()
+    }
+
+    fn set_time(&mut self, timestamp: u64) {
+        self.app.update_block(|block| {
+            block.time = Timestamp::from_seconds(timestamp);
+        });
+    }
+
+    /// Starts a sale from `start_time` that lasts a day and returns its id.
+    fn start_ido(&mut self, start_time: u64, min_tier_age: Option<u64>) -> u32 {
+        let msg = ExecuteMsg::StartIdo {
+            start_time,
+            end_time: start_time + DAY,
+            token_contract: self.token.to_string(),
+            price: Uint128::new(1),
+            soft_cap: Uint128::new(1),
+            payment: PaymentMethod::Native,
+            total_amount: Uint128::new(1000),
+            tokens_per_tier: vec![
+                Uint128::new(400),
+                Uint128::new(300),
+                Uint128::new(200),
+                Uint128::new(100)
+            ],
+            padding: None,
+            boycott_disabled: false,
+            min_tier_age,
+        };
+        let admin = Addr::unchecked(ADMIN);
+        self.app.execute_contract(admin, self.contract.clone(), &msg, &[]).unwrap();
+
+        match self.query(&(QueryMsg::IdoAmount {})) {
+            QueryResponse::IdoAmount { amount } => amount - 1,
+            answer => panic!("Unexpected answer {:?}", answer),
+        }
+    }
+
+    fn buy(&mut self, ido_id: u32, amount: u128) -> Result<AppResponse, String> {
+        let msg = ExecuteMsg::BuyTokens {
+            ido_id,
+            amount: Uint128::new(amount),
+        };
+        let funds = coins(amount, ORAI);
+        self.app
+            .execute_contract(Addr::unchecked(ALICE), self.contract.clone(), &msg, &funds)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn boycott(&mut self, ido_id: u32) -> Result<AppResponse, String> {
+        let msg = ExecuteMsg::BoycottIdo { ido_id };
+        self.app
+            .execute_contract(Addr::unchecked(ALICE), self.contract.clone(), &msg, &[])
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn query(&self, msg: &QueryMsg) -> QueryResponse {
+        self.app.wrap().query_wasm_smart(&self.contract, msg).unwrap()
+    }
+
+    fn remaining_per_tiers(&self, ido_id: u32) -> Vec<u128> {
+        match self.query(&(QueryMsg::IdoInfo { ido_id })) {
+            QueryResponse::IdoInfo { remaining_per_tiers, .. } =>
+                remaining_per_tiers
+                    .iter()
+                    .map(|v| v.u128())
+                    .collect(),
+            answer => panic!("Unexpected answer {:?}", answer),
+        }
+    }
+
+    fn balance(&self) -> u128 {
+        self.app.wrap().query_balance(ALICE, ORAI).unwrap().amount.u128()
+    }
+}
+
+fn assert_error(result: Result<AppResponse, String>, message: &str) {
+    let err = result.unwrap_err();
+    assert!(err.contains(message), "{} does not contain {}", err, message);
+}
+
+#[test]
+fn tier_reached_during_the_sale_does_not_count() {
+    let _tier_mock = lock_tier_mock();
+    let mut sale = setup();
+    let start_time = sale.now() + DAY;
+
+    set_tier(4);
+    set_tier_history(vec![(start_time + 1, 1)]);
+    let ido_id = sale.start_ido(start_time, None);
+    sale.set_time(start_time + 2);
+
+    assert_error(sale.buy(ido_id, 150), "You cannot buy more than 100 tokens");
+    sale.buy(ido_id, 100).unwrap();
+    assert_eq!(sale.remaining_per_tiers(ido_id), vec![400, 300, 200, 0]);
+}
+
+#[test]
+fn min_tier_age_needs_the_tier_for_the_whole_window() {
+    let _tier_mock = lock_tier_mock();
+    let mut sale = setup();
+    let start_time = sale.now() + 30 * DAY;
+
+    // Tier 1 three days before the start is too young for a week
+    set_tier(4);
+    set_tier_history(vec![(start_time - 3 * DAY, 1)]);
+    let young_id = sale.start_ido(start_time, Some(7 * DAY));
+    let any_age_id = sale.start_ido(start_time, None);
+    let downgraded_id = sale.start_ido(start_time + 7 * DAY, Some(7 * DAY));
+    let interrupted_id = sale.start_ido(start_time + 14 * DAY, Some(7 * DAY));
+
+    sale.set_time(start_time);
+    sale.buy(young_id, 100).unwrap();
+    assert_eq!(sale.remaining_per_tiers(young_id), vec![400, 300, 200, 0]);
+    sale.buy(any_age_id, 100).unwrap();
+    assert_eq!(sale.remaining_per_tiers(any_age_id), vec![300, 300, 200, 100]);
+
+    // Tier 1 a week before the start, but only tier 3 at the start
+    set_tier_history(vec![(start_time - 3 * DAY, 1), (start_time + 6 * DAY, 3)]);
+    sale.set_time(start_time + 7 * DAY);
+    sale.buy(downgraded_id, 100).unwrap();
+    assert_eq!(sale.remaining_per_tiers(downgraded_id), vec![400, 300, 100, 100]);
+
+    // Tier 1 at both ends of the week, but withdrawn in between
+    set_tier_history(
+        vec![(start_time - 3 * DAY, 1), (start_time + 9 * DAY, 4), (start_time + 10 * DAY, 1)]
+    );
+    sale.set_time(start_time + 14 * DAY);
+    sale.buy(interrupted_id, 100).unwrap();
+    assert_eq!(sale.remaining_per_tiers(interrupted_id), vec![400, 300, 200, 0]);
+}
+
+#[test]
+fn boycott_returns_tokens_to_the_purchase_tier() {
+    let _tier_mock = lock_tier_mock();
+    let mut sale = setup();
+    let start_time = sale.now() + DAY;
+
+    set_tier(4);
+    set_tier_history(vec![(0, 2)]);
+    let ido_id = sale.start_ido(start_time, None);
+    sale.set_time(start_time);
+    sale.buy(ido_id, 100).unwrap();
+
+    // Later purchases and the boycott keep the tier of the first purchase
+    set_tier_history(vec![(0, 1)]);
+    sale.buy(ido_id, 50).unwrap();
+    assert_eq!(sale.remaining_per_tiers(ido_id), vec![400, 150, 200, 100]);
+
+    assert_error(sale.boycott(ido_id), "A user cannot withdraw before the IDO ends");
+    sale.set_time(start_time + DAY);
+    let balance = sale.balance();
+    sale.boycott(ido_id).unwrap();
+
+    assert_eq!(sale.remaining_per_tiers(ido_id), vec![400, 300, 200, 100]);
+    assert_eq!(sale.balance(), balance + 150);
+    assert_error(sale.boycott(ido_id), "There are no purchases to boycott");
+}
@@ -4,3 +4,6 @@ pub mod tier;
 pub mod msg;
 pub mod state;
 pub mod error;
+
+#[cfg(test)]
+mod integration_tests;
@@ -77,6 +77,7 @@ pub enum ExecuteMsg {
         tokens_per_tier: Vec<Uint128>,
         padding: Option<String>,
         boycott_disabled: bool,
+        min_tier_age: Option<u64>,
     },
     BuyTokens {
         ido_id: u32,
@@ -193,6 +194,7 @@ pub enum QueryResponse {
         withdrawn: bool,
         remaining_per_tiers: Vec<Uint128>,
         boycott_disabled: bool,
+        min_tier_age: Option<u64>,
     },
     IdoListOwnedBy {
         ido_ids: Vec<u32>,
@@ -119,6 +119,9 @@ pub struct UserInfo {
     pub total_payment: u128,
     pub total_tokens_bought: u128,
     pub total_tokens_received: u128,
+    /// Tier the purchases were made with, zero before the first purchase
+    #[serde(default)]
+    pub tier: u8,
 }
 
 impl UserInfo {
@@ -151,6 +154,8 @@ pub struct Ido {
     pub total_payment: u128,
     pub withdrawn: bool,
     pub boycott_disabled: bool,
+    #[serde(default)]
+    pub min_tier_age: Option<u64>,
 }
 
 impl Ido {
@@ -241,6 +246,7 @@ impl Ido {
             soft_cap: Uint128::new(self.soft_cap),
             withdrawn: self.withdrawn,
             boycott_disabled: self.boycott_disabled,
+            min_tier_age: self.min_tier_age,
         })
     }
 }
@@ -34,6 +34,15 @@ mod query {
         UserInfo {
             address: String,
         },
+        TierAt {
+            address: String,
+            timestamp: u64,
+        },
+        TierHeld {
+            address: String,
+            start: u64,
+            end: u64,
+        },
     }
 
     // impl Query for TierContractQuery {
@@ -46,6 +55,12 @@ mod query {
         UserInfo {
             tier: u8,
         },
+        TierAt {
+            tier: u8,
+        },
+        TierHeld {
+            tier: u8,
+        },
         Config {
             admin: String,
             validators: Vec<ValidatorWithWeight>,
@@ -159,6 +174,82 @@ mod query {
         Ok(tier)
     }
 
+    fn get_tier_at_from_tier_contract(
+        deps: &Deps,
+        address: String,
+        timestamp: u64,
+        config: &Config
+    ) -> StdResult<u8> {
+        let tier_contract = config.tier_contract.to_string();
+        let tier_at = TierContractQuery::TierAt { address, timestamp };
+
+        if
+            let TierResponse::TierAt { tier } = deps.querier.query_wasm_smart(
+                tier_contract,
+                &tier_at
+            )?
+        {
+            Ok(tier)
+        } else {
+            Err(StdError::generic_err("Cannot get tier"))
+        }
+    }
+
+    /// Same as `get_tier`, but the Tier contract reports the tier held at `timestamp`.
+    pub fn get_tier_at(deps: &Deps, address: String, timestamp: u64) -> StdResult<u8> {
+        let config = Config::load(deps.storage)?;
+
+        let from_nft_contract = get_tier_from_nft_contract(deps, &address, &config)?;
+
+        let mut tier = get_tier_at_from_tier_contract(deps, address, timestamp, &config)?;
+        if let Some(nft_tier) = from_nft_contract {
+            if nft_tier < tier {
+                tier = nft_tier;
+            }
+        }
+
+        Ok(tier)
+    }
+
+    fn get_tier_held_from_tier_contract(
+        deps: &Deps,
+        address: String,
+        start: u64,
+        end: u64,
+        config: &Config
+    ) -> StdResult<u8> {
+        let tier_contract = config.tier_contract.to_string();
+        let tier_held = TierContractQuery::TierHeld { address, start, end };
+
+        if
+            let TierResponse::TierHeld { tier } = deps.querier.query_wasm_smart(
+                tier_contract,
+                &tier_held
+            )?
+        {
+            Ok(tier)
+        } else {
+            Err(StdError::generic_err("Cannot get tier"))
+        }
+    }
+
+    /// Same as `get_tier`, but the Tier contract reports the worst tier held during the whole
+    /// of `[start, end]`.
+    pub fn get_tier_held(deps: &Deps, address: String, start: u64, end: u64) -> StdResult<u8> {
+        let config = Config::load(deps.storage)?;
+
+        let from_nft_contract = get_tier_from_nft_contract(deps, &address, &config)?;
+
+        let mut tier = get_tier_held_from_tier_contract(deps, address, start, end, &config)?;
+        if let Some(nft_tier) = from_nft_contract {
+            if nft_tier < tier {
+                tier = nft_tier;
+            }
+        }
+
+        Ok(tier)
+    }
+
     pub fn get_min_tier(deps: &Deps, config: &Config) -> StdResult<u8> {
         let tier_contract = config.tier_contract.to_string();
         let user_info = TierContractQuery::Config {};
@@ -184,6 +275,7 @@ pub mod manual {
 
     static TIER: Mutex<u8> = Mutex::new(0);
     static MIN_TIER: Mutex<u8> = Mutex::new(4);
+    static TIER_HISTORY: Mutex<Vec<(u64, u8)>> = Mutex::new(Vec::new());
 
     pub fn set_tier(tier: u8) {
         let mut tier_lock = TIER.lock().unwrap();
@@ -195,11 +287,39 @@ pub mod manual {
         *tier_lock = tier;
     }
 
+    /// Tiers held from the given timestamps on, in ascending order. Before the first one
+    /// `get_tier_at` returns the tier of `set_tier`.
+    pub fn set_tier_history(history: Vec<(u64, u8)>) {
+        let mut history_lock = TIER_HISTORY.lock().unwrap();
+        *history_lock = history;
+    }
+
     pub fn get_tier(_deps: &Deps, _address: String) -> StdResult<u8> {
         let tier_lock = TIER.lock().unwrap();
         Ok(*tier_lock)
     }
 
+    pub fn get_tier_at(_deps: &Deps, _address: String, timestamp: u64) -> StdResult<u8> {
+        let history_lock = TIER_HISTORY.lock().unwrap();
+        let tier = history_lock
+            .iter()
+            .rev()
+            .find(|(from, _)| *from <= timestamp)
+            .map(|(_, tier)| *tier);
+        Ok(tier.unwrap_or(*TIER.lock().unwrap()))
+    }
+
+    pub fn get_tier_held(deps: &Deps, address: String, start: u64, end: u64) -> StdResult<u8> {
+        let tier = get_tier_at(deps, address, start)?;
+        let history_lock = TIER_HISTORY.lock().unwrap();
+        let worst_tier = history_lock
+            .iter()
+            .filter(|(from, _)| *from > start && *from <= end)
+            .map(|(_, tier)| *tier)
+            .fold(tier, u8::max);
+        Ok(worst_tier)
+    }
+
     pub fn get_min_tier(_deps: &Deps, _config: &Config) -> StdResult<u8> {
         let tier_lock = MIN_TIER.lock().unwrap();
         Ok(*tier_lock)
@@ -218,6 +338,12 @@ pub mod manual {
 #[cfg(not(test))]
 pub use query::get_tier;
 
+#[cfg(not(test))]
+pub use query::get_tier_at;
+
+#[cfg(not(test))]
+pub use query::get_tier_held;
+
 #[cfg(not(test))]
 pub use query::get_min_tier;
 
@@ -227,6 +353,12 @@ pub use query::get_tier_from_nft_contract;
 #[cfg(test)]
 pub use manual::get_tier;
 
+#[cfg(test)]
+pub use manual::get_tier_at;
+
+#[cfg(test)]
+pub use manual::get_tier_held;
+
 #[cfg(test)]
 pub use manual::get_min_tier;
 
@@ -1,23 +1,34 @@
-use crate::state::Config;
-use cosmwasm_std::DepsMut;
-use cosmwasm_std::StdResult;
+use crate::contract::ORAI;
+use crate::msg::{ OraiswapContract, PriceOracleSource };
+use crate::state::{ Config, PriceObservation, PRICE_BASELINE, PRICE_OBSERVATIONS };
+use cosmwasm_std::{ Deps, Env, StdError, StdResult, Storage, Uint128 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-pub struct OraiPriceOracle {
-    exchange_rate: u128,
+pub const MIN_OBSERVATION_INTERVAL: u64 = 60;
+/// Longest time a single observation is weighted for, so one sample cannot carry the
+/// average while keepers are idle.
+pub const MAX_OBSERVATION_WEIGHT: u64 = 5 * MIN_OBSERVATION_INTERVAL;
+/// Percentage of the TWAP window the weighted observations have to cover.
+pub const MIN_TWAP_COVERAGE: u64 = 50;
+/// The circuit breaker baseline moves at most once per period.
+pub const BASELINE_PERIOD: u64 = 24 * 60 * 60;
+
+/// Source of the ORAI price. The exchange rate is the amount of micro USD paid for 1 ORAI,
+/// the timestamp tells when the backend last updated it.
+pub trait PriceOracle {
+    fn quote(&self, deps: Deps, env: &Env) -> StdResult<PriceObservation>;
 }
 
-impl OraiPriceOracle {
-    pub const ZERO_12: u128 = 1_000_000_000_000;
-    pub const ZERO_6: u128 = 1_000_000;
+/// Simulates a swap of 1 ORAI to USDT on the Oraiswap router.
+pub struct OraiswapRouter {
+    pub contract: OraiswapContract,
+}
 
-    pub fn new(deps: &DepsMut) -> StdResult<Self> {
-        let config = Config::load(deps.storage)?;
-        let orai_swap_router_contract = config.oraiswap_contract.orai_swap_router_contract;
-        let native_token = NativeToken::new("orai".to_string());
+impl PriceOracle for OraiswapRouter {
+    fn quote(&self, deps: Deps, env: &Env) -> StdResult<PriceObservation> {
+        let native_token = NativeToken::new(ORAI.to_string());
         let offer_asset_info = OfferAssetInfo::new(native_token);
-        let usdt_contract_address = config.oraiswap_contract.usdt_contract;
         let msg = SwapContractMessage {
             simulate_swap_operations: SwapContractMessageContent {
                 offer_amount: 1000000,
@@ -26,19 +37,244 @@ impl OraiPriceOracle {
                         offer_asset_info: offer_asset_info,
                         ask_asset_info: AskAssetInfo {
                             token: UsdtContractAddr {
-                                contract_addr: usdt_contract_address,
+                                contract_addr: self.contract.usdt_contract.clone(),
                             },
                         },
                     },
                 }],
             },
         };
-        let response: ExchangeRateResponse = deps
-            .querier
-            .query_wasm_smart(orai_swap_router_contract, &msg)?;
-        let exchange_rate = response.amount;
+        let response: ExchangeRateResponse = deps.querier.query_wasm_smart(
+            self.contract.orai_swap_router_contract.clone(),
+            &msg
+        )?;
+
+        Ok(PriceObservation {
+            exchange_rate: response.amount,
+            timestamp: env.block.time.seconds(),
+        })
+    }
+}
+
+/// Reads the ORAI/USD pair from a Band standard reference contract.
+pub struct BandStdReference {
+    pub contract: String,
+}
+
+impl PriceOracle for BandStdReference {
+    fn quote(&self, deps: Deps, _env: &Env) -> StdResult<PriceObservation> {
+        let msg = BandQueryMsg::GetReferenceData {
+            base_symbol: "ORAI".to_string(),
+            quote_symbol: "USD".to_string(),
+        };
+        let response: ReferenceData = deps.querier.query_wasm_smart(self.contract.clone(), &msg)?;
+
+        // Band rates have 18 decimals, micro USD have 6
+        Ok(PriceObservation {
+            exchange_rate: response.rate.u128().checked_div(OraiPriceOracle::ZERO_12).unwrap(),
+            timestamp: response.last_updated_base.min(response.last_updated_quote),
+        })
+    }
+}
+
+/// Admin-set exchange rate, intended for testnets.
+pub struct FixedPrice {
+    pub exchange_rate: u128,
+}
+
+impl PriceOracle for FixedPrice {
+    fn quote(&self, _deps: Deps, env: &Env) -> StdResult<PriceObservation> {
+        Ok(PriceObservation {
+            exchange_rate: self.exchange_rate,
+            timestamp: env.block.time.seconds(),
+        })
+    }
+}
+
+pub fn price_oracle(config: &Config) -> Box<dyn PriceOracle> {
+    match &config.price_oracle {
+        PriceOracleSource::Oraiswap {} =>
+            Box::new(OraiswapRouter {
+                contract: config.oraiswap_contract.clone(),
+            }),
+        PriceOracleSource::Band { contract } =>
+            Box::new(BandStdReference {
+                contract: contract.clone(),
+            }),
+        PriceOracleSource::Fixed { exchange_rate } =>
+            Box::new(FixedPrice {
+                exchange_rate: exchange_rate.u128(),
+            }),
+    }
+}
+
+/// Averages the recorded observations over the last `window` seconds. Every observation
+/// is weighted by the time it stayed the latest one, at most `MAX_OBSERVATION_WEIGHT`,
+/// so a sample recorded in the current block has no weight at all. The weighted time has
+/// to cover `MIN_TWAP_COVERAGE` percent of the window. The result is as old as the latest
+/// sample.
+pub fn time_weighted_rate(
+    storage: &dyn Storage,
+    window: u64,
+    now: u64
+) -> StdResult<PriceObservation> {
+    let window_start = now.saturating_sub(window);
+    let observations = PRICE_OBSERVATIONS.iter(storage)?.collect::<StdResult<Vec<_>>>()?;
+
+    let mut weighted_sum: u128 = 0;
+    let mut total_time: u64 = 0;
+    for (index, observation) in observations.iter().enumerate() {
+        let from = observation.timestamp.max(window_start);
+        let to = observations
+            .get(index + 1)
+            .map(|next| next.timestamp)
+            .unwrap_or(now);
+
+        if to <= from {
+            continue;
+        }
+
+        let elapsed = (to - from).min(MAX_OBSERVATION_WEIGHT);
+        weighted_sum = weighted_sum
+            .checked_add(observation.exchange_rate.checked_mul(elapsed as u128).unwrap())
+            .unwrap();
+        total_time += elapsed;
+    }
+
+    if total_time == 0 {
+        return Err(StdError::generic_err("Not enough price observations"));
+    }
+
+    if (total_time as u128) * 100 < (window as u128) * (MIN_TWAP_COVERAGE as u128) {
+        return Err(StdError::generic_err("Price observations cover too little of the TWAP window"));
+    }
+
+    Ok(PriceObservation {
+        exchange_rate: weighted_sum.checked_div(total_time as u128).unwrap(),
+        timestamp: observations.last().unwrap().timestamp,
+    })
+}
+
+/// Stores a new spot observation and drops the ones that no longer affect the average.
+pub fn record_observation(
+    storage: &mut dyn Storage,
+    observation: PriceObservation,
+    window: u64
+) -> StdResult<()> {
+    if let Some(last) = PRICE_OBSERVATIONS.back(storage)? {
+        if observation.timestamp < last.timestamp.saturating_add(MIN_OBSERVATION_INTERVAL) {
+            return Err(StdError::generic_err("Price was recorded too recently"));
+        }
+    }
+
+    PRICE_OBSERVATIONS.push_back(storage, &observation)?;
+
+    // Keep the last observation made before the window, it covers the window start
+    let window_start = observation.timestamp.saturating_sub(window);
+    while let Some(second) = PRICE_OBSERVATIONS.get(storage, 1)? {
+        if second.timestamp > window_start {
+            break;
+        }
+        PRICE_OBSERVATIONS.pop_front(storage)?;
+    }
+
+    Ok(())
+}
+
+/// Moves the circuit breaker baseline to `exchange_rate`, at most once per `BASELINE_PERIOD`
+/// and by at most `max_price_deviation` percent. Deposits can therefore walk the price by
+/// no more than the allowed deviation per period, a larger move waits for keepers to
+/// record prices over several periods or for the admin to reset the baseline.
+pub fn move_baseline(
+    storage: &mut dyn Storage,
+    config: &Config,
+    exchange_rate: u128,
+    now: u64
+) -> StdResult<()> {
+    let baseline = PRICE_BASELINE.may_load(storage)?;
+    let exchange_rate = match (&baseline, config.max_price_deviation) {
+        (Some(baseline), _) if now < baseline.timestamp.saturating_add(BASELINE_PERIOD) => {
+            return Ok(());
+        }
+        (Some(baseline), Some(max_price_deviation)) => {
+            let max_step = Uint128::from(baseline.exchange_rate)
+                .multiply_ratio(max_price_deviation, 100_u128)
+                .u128();
+            exchange_rate.clamp(
+                baseline.exchange_rate.saturating_sub(max_step),
+                baseline.exchange_rate.saturating_add(max_step)
+            )
+        }
+        _ => exchange_rate,
+    };
+
+    PRICE_BASELINE.save(storage, &(PriceObservation {
+        exchange_rate,
+        timestamp: now,
+    }))
+}
+
+pub struct OraiPriceOracle {
+    exchange_rate: u128,
+    timestamp: u64,
+}
+
+impl OraiPriceOracle {
+    pub const ZERO_12: u128 = 1_000_000_000_000;
+    pub const ZERO_6: u128 = 1_000_000;
+
+    pub fn new(deps: Deps, env: &Env) -> StdResult<Self> {
+        let config = Config::load(deps.storage)?;
+        let quote = if config.twap_window == 0 {
+            price_oracle(&config).quote(deps, env)?
+        } else {
+            time_weighted_rate(deps.storage, config.twap_window, env.block.time.seconds())?
+        };
+
+        if quote.exchange_rate == 0 {
+            return Err(StdError::generic_err("Price oracle returned zero exchange rate"));
+        }
 
-        Ok(OraiPriceOracle { exchange_rate })
+        Ok(OraiPriceOracle {
+            exchange_rate: quote.exchange_rate,
+            timestamp: quote.timestamp,
+        })
+    }
+
+    pub fn exchange_rate(&self) -> u128 {
+        self.exchange_rate
+    }
+
+    /// Circuit breaker: rejects quotes that are too old or moved too far from the baseline,
+    /// then lets the baseline follow this quote, see `move_baseline`.
+    pub fn accept(&self, storage: &mut dyn Storage, config: &Config, now: u64) -> StdResult<()> {
+        self.check(storage, config, now)?;
+        move_baseline(storage, config, self.exchange_rate, now)
+    }
+
+    /// Same limits as `accept`, without moving the baseline.
+    pub fn check(&self, storage: &dyn Storage, config: &Config, now: u64) -> StdResult<()> {
+        if let Some(max_price_age) = config.max_price_age {
+            if now.saturating_sub(self.timestamp) > max_price_age {
+                return Err(StdError::generic_err("Price quote is too old"));
+            }
+        }
+
+        if let Some(max_price_deviation) = config.max_price_deviation {
+            if let Some(baseline) = PRICE_BASELINE.may_load(storage)? {
+                let deviation = self.exchange_rate.abs_diff(baseline.exchange_rate);
+                let allowed = baseline.exchange_rate.checked_mul(max_price_deviation).unwrap();
+                if deviation.checked_mul(100).unwrap() > allowed {
+                    let err_msg = format!(
+                        "Price moved more than {}% from the last accepted rate",
+                        max_price_deviation
+                    );
+                    return Err(StdError::generic_err(err_msg));
+                }
+            }
+        }
+
+        Ok(())
     }
 
     pub fn usd_amount(&self, orai: u128) -> u128 {
@@ -142,3 +378,20 @@ pub struct Amount {
 pub struct ExchangeRateResponse {
     pub amount: u128,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BandQueryMsg {
+    GetReferenceData {
+        base_symbol: String,
+        quote_symbol: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ReferenceData {
+    pub rate: Uint128,
+    pub last_updated_base: u64,
+    pub last_updated_quote: u64,
+}
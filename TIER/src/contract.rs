@@ -11,18 +11,24 @@ use cosmwasm_std::{
     Deps,
     DepsMut,
     Env,
+    Event,
     FullDelegation,
     MessageInfo,
+    Reply,
     Response,
     StdResult,
+    Storage,
     SubMsg,
+    SubMsgResult,
     Uint128,
+    WasmMsg,
 };
 
 use cosmwasm_std::DistributionMsg;
+use cw_storage_plus::Bound;
 use cosmwasm_std::StakingMsg;
 
-use crate::band::OraiPriceOracle;
+use crate::band::{ self, OraiPriceOracle };
 // use crate::utils;
 use crate::error::ContractError;
 use crate::msg::{
@@ -31,21 +37,60 @@ use crate::msg::{
     ExecuteMsg,
     ExecuteResponse,
     InstantiateMsg,
+    LockBoost,
     OraiswapContract,
+    PriceOracleSource,
     QueryMsg,
     QueryResponse,
+    Permission,
     ResponseStatus,
+    SerializedApproval,
+    SerializedEpoch,
+    SerializedFundedDeposit,
+    SerializedPriceObservation,
+    SerializedTierStats,
     SerializedUnbonds,
+    SerializedUserInfo,
+    SerializedUserEpoch,
     SerializedWithdrawals,
+    TierHookMsg,
+    TierPolicy,
+    TierRequirement,
+    ValidatorHealth,
+    ValidatorState,
     ValidatorWithWeight,
 };
 use crate::state::{
     self,
+    Approval,
     Config,
+    DelegationState,
+    PendingBatch,
+    PriceObservation,
+    TierCheckpoint,
+    UnbondEpoch,
     UserUnbond,
     UserWithdrawal,
+    WalletLink,
+    APPROVALS,
     CONFIG_ITEM,
-    UNBOND_LIST,
+    DELEGATION_STATE,
+    HOOKS,
+    LINKED_OWNERS,
+    LINKS,
+    LINK_PROPOSALS,
+    DEPOSIT_FUNDERS,
+    FUNDED_DEPOSITS,
+    LIFETIME_DEPOSITS,
+    PRICE_BASELINE,
+    TIER_HISTORY,
+    TIER_HOLDERS,
+    TIER_STATS,
+    EPOCH_UNBONDS,
+    PENDING_BATCH,
+    PRICE_OBSERVATIONS,
+    UNBOND_EPOCH,
+    UNBOND_EPOCHS,
     USER_INFOS,
     USER_TOTAL_DELEGATED,
     WITHDRAWALS_LIST,
@@ -56,6 +101,8 @@ use cosmwasm_std::StdError;
 pub const UNBOUND_TIME: u64 = 21 * 24 * 60 * 60;
 pub const BATCH_PERIOD: u64 = 5 * 24 * 60 * 60;
 pub const MAX_UNIX_TIMESTAMP: u64 = 2147483647;
+pub const HOOK_REPLY_ID: u64 = 1;
+pub const HOOK_GAS_LIMIT: u64 = 500_000;
 pub const ORAI: &str = "orai";
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -86,25 +133,48 @@ pub fn instantiate(
 
     // Check if the sum of the validators' weights is 100
     let validators = msg.validators;
-    let total_weight: u128 = validators
-        .iter()
-        .map(|v| v.weight)
-        .sum();
+    assert_validator_weights(&validators)?;
 
-    if total_weight != 100 {
+    // 10^decimals base units are worth 1 USD and have to fit the deposit maths
+    let stable_denom = msg.stable_denom.unwrap_or_default();
+    if stable_denom.iter().any(|s| s.denom == ORAI || s.decimals > 18) {
         return Err(
-            ContractError::Std(StdError::generic_err("The sum of the total weight must be 100!"))
+            ContractError::Std(
+                StdError::generic_err("Stable denoms need at most 18 decimals and cannot be ORAI")
+            )
         );
     }
 
+    let reward_fee = msg.reward_fee.unwrap_or(0);
+    if reward_fee > 100 {
+        return Err(ContractError::Std(StdError::generic_err("Reward fee cannot exceed 100%")));
+    }
+
+    let lock_boosts = msg.lock_boosts.unwrap_or_default();
+    assert_lock_boosts(&lock_boosts)?;
+
+    if msg.tier_validity == Some(0) {
+        return Err(ContractError::Std(StdError::generic_err("Tier validity should not be ZERO!")));
+    }
+
     let admin = msg.admin.unwrap_or("".to_string());
+    let treasury = msg.treasury.unwrap_or(admin.clone());
     let initial_config: Config = Config {
         status: ContractStatus::Active as u8,
         admin: admin,
         validators,
         usd_deposits: deposits,
         oraiswap_contract: msg.oraiswap_contract,
-        stable_denom: msg.stable_denom.unwrap_or_default(),
+        stable_denom,
+        price_oracle: msg.price_oracle.unwrap_or_default(),
+        twap_window: msg.twap_window.unwrap_or(0),
+        max_price_deviation: msg.max_price_deviation,
+        max_price_age: msg.max_price_age,
+        reward_fee,
+        treasury,
+        tier_policy: msg.tier_policy.unwrap_or_default(),
+        lock_boosts,
+        tier_validity: msg.tier_validity,
     };
 
     CONFIG_ITEM.save(deps.storage, &initial_config)?;
@@ -125,14 +195,54 @@ pub fn execute(
         ExecuteMsg::ChangeStatus { status, .. } => try_change_status(deps, env, info, status),
         ExecuteMsg::ChangeOraiswap { oraiswap_router_contract, usdt_contract } =>
             try_change_oraiswap(deps, env, info, oraiswap_router_contract, usdt_contract),
-        ExecuteMsg::Deposit { .. } => try_deposit(deps, env, info),
-        ExecuteMsg::Withdraw { .. } => try_withdraw(deps, env, info),
-        ExecuteMsg::BatchUnbond { .. } => try_batch_unbond(deps, env),
-        ExecuteMsg::Claim { recipient, start, limit, .. } =>
-            try_claim(deps, env, info, recipient, start, limit),
-        ExecuteMsg::WithdrawRewards { recipient, .. } => {
-            try_withdraw_rewards(deps, env, info, recipient)
-        }
+        ExecuteMsg::ChangePriceOracle { price_oracle } =>
+            try_change_price_oracle(deps, env, info, price_oracle),
+        ExecuteMsg::ChangeTwapWindow { twap_window } =>
+            try_change_twap_window(deps, env, info, twap_window),
+        ExecuteMsg::RecordPrice {} => try_record_price(deps, env),
+        ExecuteMsg::ChangePriceLimits { max_price_deviation, max_price_age } =>
+            try_change_price_limits(deps, env, info, max_price_deviation, max_price_age),
+        ExecuteMsg::ResetPriceBaseline { exchange_rate } =>
+            try_reset_price_baseline(deps, env, info, exchange_rate),
+        ExecuteMsg::ChangeRewardFee { reward_fee, treasury } =>
+            try_change_reward_fee(deps, env, info, reward_fee, treasury),
+        ExecuteMsg::ChangeTierPolicy { tier_policy } =>
+            try_change_tier_policy(deps, env, info, tier_policy),
+        ExecuteMsg::ChangeLockBoosts { lock_boosts } =>
+            try_change_lock_boosts(deps, env, info, lock_boosts),
+        ExecuteMsg::ChangeTierValidity { tier_validity } =>
+            try_change_tier_validity(deps, env, info, tier_validity),
+        ExecuteMsg::AddHook { contract_addr } => try_add_hook(deps, env, info, contract_addr),
+        ExecuteMsg::RemoveHook { contract_addr } =>
+            try_remove_hook(deps, env, info, contract_addr),
+        ExecuteMsg::Deposit { lock_duration } =>
+            try_deposit(deps, env, info, None, false, lock_duration),
+        ExecuteMsg::DepositFor { beneficiary, funder_withdraws, lock_duration } =>
+            try_deposit(deps, env, info, Some(beneficiary), funder_withdraws, lock_duration),
+        ExecuteMsg::Withdraw { amount, target_tier, owner } =>
+            try_withdraw(deps, env, info, amount, target_tier, owner),
+        ExecuteMsg::BatchUnbond { limit } => try_batch_unbond(deps, env, limit),
+        ExecuteMsg::Claim { recipient, start, limit, owner } =>
+            try_claim(deps, env, info, recipient, start, limit, owner),
+        ExecuteMsg::HarvestRewards {} => try_harvest_rewards(deps, env),
+        ExecuteMsg::ClaimRewards { recipient, owner } =>
+            try_claim_rewards(deps, env, info, recipient, owner),
+        ExecuteMsg::SetCompounding { enabled, owner } =>
+            try_set_compounding(deps, env, info, enabled, owner),
+        ExecuteMsg::RefreshTier { address } => try_refresh_tier(deps, env, info, address),
+        ExecuteMsg::Renew { owner } => try_renew(deps, env, info, owner),
+        ExecuteMsg::Approve { operator, permissions, expires } =>
+            try_approve(deps, env, info, operator, permissions, expires),
+        ExecuteMsg::Revoke { operator } => try_revoke(deps, env, info, operator),
+        ExecuteMsg::ProposeLink { address } => try_propose_link(deps, env, info, address),
+        ExecuteMsg::AcceptLink { owner } => try_accept_link(deps, env, info, owner),
+        ExecuteMsg::Unlink {} => try_unlink(deps, env, info),
+        ExecuteMsg::AddValidator { address } => try_add_validator(deps, env, info, address),
+        ExecuteMsg::RemoveValidator { address } => try_remove_validator(deps, env, info, address),
+        ExecuteMsg::SetValidatorWeights { validators } =>
+            try_set_validator_weights(deps, env, info, validators),
+        ExecuteMsg::Rebalance {} => try_rebalance(deps, env),
+        ExecuteMsg::CheckValidators {} => try_check_validators(deps, env),
         ExecuteMsg::Redelegate {
             new_validator_address,
             old_validator_address,
@@ -155,15 +265,61 @@ pub fn execute(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match (msg.id, msg.result) {
+        (HOOK_REPLY_ID, SubMsgResult::Err(error)) =>
+            Ok(
+                Response::new()
+                    .add_attribute("action", "hook_failed")
+                    .add_attribute("error", error)
+            ),
+        (HOOK_REPLY_ID, SubMsgResult::Ok(_)) => Ok(Response::new()),
+        (id, _) =>
+            Err(ContractError::Std(StdError::generic_err(format!("Unknown reply id {}", id)))),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
-        QueryMsg::UserInfo { address } => to_json_binary(&query_user_info(deps, address)?),
+        QueryMsg::Hooks {} => to_json_binary(&query_hooks(deps)?),
+        QueryMsg::AllUsers { start_after, limit, tier } =>
+            to_json_binary(&query_all_users(deps, env, start_after, limit, tier)?),
+        QueryMsg::TierStats {} => to_json_binary(&query_tier_stats(deps)?),
+        QueryMsg::State {} => to_json_binary(&query_state(deps, env)?),
+        QueryMsg::UserInfo { address } => to_json_binary(&query_user_info(deps, env, address)?),
         QueryMsg::UserTotalDelegated { address } =>
             to_json_binary(&query_user_total_delegated(deps, address)?),
         QueryMsg::Withdrawals { address, start, limit } =>
             to_json_binary(&query_withdrawals(deps, address, start, limit)?),
         QueryMsg::Unbonds {} => to_json_binary(&query_unbonds(deps)?),
+        QueryMsg::CurrentEpoch {} => to_json_binary(&query_current_epoch(deps)?),
+        QueryMsg::Epochs { start_after, limit } =>
+            to_json_binary(&query_epochs(deps, start_after, limit)?),
+        QueryMsg::UserEpochs { address } => to_json_binary(&query_user_epochs(deps, address)?),
+        QueryMsg::Approval { owner, operator } =>
+            to_json_binary(&query_approval(deps, owner, operator)?),
+        QueryMsg::Approvals { owner, start_after, limit } =>
+            to_json_binary(&query_approvals(deps, owner, start_after, limit)?),
+        QueryMsg::Link { address } => to_json_binary(&query_link(deps, address)?),
+        QueryMsg::FundedDeposits { funder, start_after, limit } =>
+            to_json_binary(&query_funded_deposits(deps, funder, start_after, limit)?),
+        QueryMsg::PriceObservations {} => to_json_binary(&query_price_observations(deps)?),
+        QueryMsg::DelegationState {} => to_json_binary(&query_delegation_state(deps)?),
+        QueryMsg::ValidatorsHealth {} => to_json_binary(&query_validators_health(deps, env)?),
+        QueryMsg::Rewards { address } => to_json_binary(&query_rewards(deps, address)?),
+        QueryMsg::TierAt { address, timestamp } =>
+            to_json_binary(&query_tier_at(deps, address, timestamp)?),
+        QueryMsg::TierHeld { address, start, end } =>
+            to_json_binary(&query_tier_held(deps, address, start, end)?),
+        QueryMsg::PreviewTier { address } =>
+            to_json_binary(&query_preview_tier(deps, env, address)?),
+        QueryMsg::Account { address } => to_json_binary(&query_account(deps, env, address)?),
+        QueryMsg::SimulateDeposit { address, amount, denom } =>
+            to_json_binary(&query_simulate_deposit(deps, env, address, amount, denom)?),
+        QueryMsg::SimulateWithdraw { address, amount, target_tier } =>
+            to_json_binary(&query_simulate_withdraw(deps, env, address, amount, target_tier)?),
     }
 }
 
@@ -257,417 +413,1729 @@ pub fn try_change_oraiswap(
     Ok(Response::new().add_attribute("action", "changed oraiswap contracts"))
 }
 
-pub fn get_received_funds(_deps: &DepsMut, info: &MessageInfo) -> Result<Coin, ContractError> {
-    let config = CONFIG_ITEM.load(_deps.storage)?;
-    config.assert_contract_active()?;
+pub fn try_change_price_oracle(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    price_oracle: PriceOracleSource
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-    match info.funds.get(0) {
-        None => {
-            return Err(ContractError::Std(StdError::generic_err("No Funds")));
+    match &price_oracle {
+        PriceOracleSource::Oraiswap {} => {}
+        PriceOracleSource::Band { contract } => {
+            deps.api.addr_validate(contract)?;
         }
-        Some(received) => {
-            /* Amount of tokens received cannot be zero */
-            if received.amount.is_zero() {
-                return Err(ContractError::Std(StdError::generic_err("Not Allow Zero Amount")));
+        PriceOracleSource::Fixed { exchange_rate } => {
+            if exchange_rate.is_zero() {
+                return Err(
+                    ContractError::Std(StdError::generic_err("Exchange rate should not be ZERO!"))
+                );
             }
-
-            /* Allow to receive only token denomination defined
-            on contract instantiation "config.stable_denom" */
-            if
-                received.denom.clone() != "orai" &&
-                config.stable_denom.contains(&received.denom.clone()) == false
-            {
-                return Err(ContractError::Std(StdError::generic_err("Unsopported token")));
-            }
-
-            /* Only one token can be received */
-            if info.funds.len() > 1 {
-                return Err(ContractError::Std(StdError::generic_err("Not Allowed Multiple Funds")));
-            }
-            Ok(received.clone())
         }
     }
-}
 
-pub fn try_deposit(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
-    let config = CONFIG_ITEM.load(deps.storage)?;
-    config.assert_contract_active()?;
+    if config.price_oracle == price_oracle {
+        return Err(
+            ContractError::Std(StdError::generic_err("Trying to change to the same price oracle."))
+        );
+    }
 
-    let received_funds = get_received_funds(&deps, &info)?;
+    config.price_oracle = price_oracle;
+    config.save(deps.storage)?;
 
-    let orai_deposit = received_funds.amount.u128();
+    Ok(Response::new().add_attribute("action", "changed price oracle"))
+}
 
-    let orai_price_ocracle = OraiPriceOracle::new(&deps)?;
+pub fn try_change_twap_window(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    twap_window: u64
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-    let usd_deposit: u128 = orai_price_ocracle.usd_amount(orai_deposit);
+    config.twap_window = twap_window;
+    config.save(deps.storage)?;
 
-    let sender = info.sender.to_string();
-    let min_tier = config.min_tier();
+    Ok(Response::new().add_attribute("action", "changed twap window"))
+}
 
-    let mut user_info = USER_INFOS.may_load(deps.storage, sender)?.unwrap_or(state::UserInfo {
-        tier: min_tier,
-        ..Default::default()
-    });
-    let current_tier = user_info.tier;
-    let old_usd_deposit = user_info.usd_deposit;
-    let old_orai_deposit = user_info.orai_deposit;
-    let new_usd_deposit = old_usd_deposit.checked_add(usd_deposit).unwrap();
+pub fn try_record_price(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
 
-    let new_tier = config.tier_by_deposit(new_usd_deposit);
+    let quote = band::price_oracle(&config).quote(deps.as_ref(), &env)?;
+    let exchange_rate = quote.exchange_rate;
+    if exchange_rate == 0 {
+        return Err(
+            ContractError::Std(StdError::generic_err("Price oracle returned zero exchange rate"))
+        );
+    }
 
-    if current_tier == new_tier {
-        if current_tier == config.max_tier() {
-            return Err(ContractError::Std(StdError::generic_err("Reached max tier")));
-        }
+    let observation = PriceObservation {
+        exchange_rate,
+        timestamp: env.block.time.seconds(),
+    };
+    // Keepers also move the circuit breaker baseline, within its limits
+    band::move_baseline(deps.storage, &config, exchange_rate, observation.timestamp)?;
+    band::record_observation(deps.storage, observation, config.twap_window)?;
 
-        let next_tier = current_tier.checked_sub(1).unwrap();
-        let next_tier_deposit: u128 = config.deposit_by_tier(next_tier);
+    let answer = to_json_binary(
+        &(ExecuteResponse::RecordPrice {
+            exchange_rate: Uint128::new(exchange_rate),
+            status: ResponseStatus::Success,
+        })
+    )?;
 
-        let expected_deposit_usd = next_tier_deposit.checked_sub(old_usd_deposit).unwrap();
-        let expected_deposit_orai = orai_price_ocracle.orai_amount(expected_deposit_usd);
+    Ok(
+        Response::new()
+            .set_data(answer)
+            .add_attribute("action", "recorded price")
+            .add_attribute("exchange_rate", exchange_rate.to_string())
+    )
+}
 
-        let err_msg = format!(
-            "You should deposit at least {} USD ({} ORAI)",
-            expected_deposit_usd,
-            expected_deposit_orai
-        );
+pub fn try_change_price_limits(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_price_deviation: Option<u128>,
+    max_price_age: Option<u64>
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-        return Err(ContractError::Std(StdError::generic_err(&err_msg)));
+    if max_price_deviation == Some(0) {
+        return Err(
+            ContractError::Std(StdError::generic_err("Price deviation limit should not be ZERO!"))
+        );
     }
 
-    let mut messages: Vec<SubMsg> = Vec::with_capacity(2);
-    let new_tier_deposit = config.deposit_by_tier(new_tier);
+    config.max_price_deviation = max_price_deviation;
+    config.max_price_age = max_price_age;
+    config.save(deps.storage)?;
 
-    // let usd_refund = new_usd_deposit.checked_sub(new_tier_deposit).unwrap();
-    let orai_refund = orai_deposit
-        .checked_sub(orai_price_ocracle.orai_amount(new_tier_deposit - old_usd_deposit))
-        .unwrap();
+    Ok(Response::new().add_attribute("action", "changed price limits"))
+}
 
-    if orai_refund != 0 {
-        // orai_deposit = orai_deposit.checked_sub(orai_refund).unwrap();
+pub fn try_reset_price_baseline(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    exchange_rate: Option<Uint128>
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-        let send_msg = BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: coins(orai_refund, ORAI),
-        };
+    // Without an explicit rate the current quote of the active backend is accepted
+    let exchange_rate = match exchange_rate {
+        Some(exchange_rate) => exchange_rate.u128(),
+        None => OraiPriceOracle::new(deps.as_ref(), &env)?.exchange_rate(),
+    };
 
-        let msg = CosmosMsg::Bank(send_msg);
-        messages.push(SubMsg::new(msg));
+    if exchange_rate == 0 {
+        return Err(ContractError::Std(StdError::generic_err("Exchange rate should not be ZERO!")));
     }
-    let old_orai_deposit = user_info.orai_deposit;
-    user_info.tier = new_tier;
-    user_info.timestamp = env.block.time.seconds();
-    user_info.usd_deposit = new_tier_deposit;
-    // user_info.orai_deposit = user_info.orai_deposit.checked_add(orai_deposit).unwrap();
-    let orai_deposit = orai_price_ocracle.orai_amount(user_info.usd_deposit);
-    user_info.orai_deposit = orai_deposit;
 
-    // Calculate user's total delegated amount
-    let mut user_total_delegated = USER_TOTAL_DELEGATED.may_load(
-        deps.storage,
-        info.sender.to_string()
-    )?.unwrap_or_default();
+    let baseline = PriceObservation {
+        exchange_rate,
+        timestamp: env.block.time.seconds(),
+    };
+    PRICE_BASELINE.save(deps.storage, &baseline)?;
 
-    user_total_delegated = user_total_delegated
-        .checked_add(Uint128::from(orai_deposit))
-        .unwrap()
-        .checked_sub(Uint128::from(old_orai_deposit))
-        .unwrap();
+    Ok(
+        Response::new()
+            .add_attribute("action", "reset price baseline")
+            .add_attribute("exchange_rate", exchange_rate.to_string())
+    )
+}
 
-    USER_TOTAL_DELEGATED.save(deps.storage, info.sender.to_string(), &user_total_delegated)?;
-    //////////////////////////////////////////
+pub fn try_change_reward_fee(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    reward_fee: u128,
+    treasury: Option<String>
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-    USER_INFOS.save(deps.storage, info.sender.to_string(), &user_info)?;
+    if reward_fee > 100 {
+        return Err(ContractError::Std(StdError::generic_err("Reward fee cannot exceed 100%")));
+    }
 
-    let validators = config.validators;
+    config.reward_fee = reward_fee;
+    if let Some(treasury) = treasury {
+        config.treasury = treasury;
+    }
+    config.save(deps.storage)?;
 
-    for validator in validators {
-        let individual_amount =
-            (user_info.orai_deposit.checked_sub(old_orai_deposit).unwrap() * validator.weight) /
-            100;
-        let delegate_msg = StakingMsg::Delegate {
-            validator: validator.address,
-            amount: coin(individual_amount, ORAI),
-        };
+    Ok(Response::new().add_attribute("action", "changed reward fee"))
+}
 
-        let msg: CosmosMsg = CosmosMsg::Staking(delegate_msg);
-        messages.push(SubMsg::new(msg));
+pub fn try_change_tier_policy(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tier_policy: TierPolicy
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
     }
 
-    let answer = to_json_binary(
-        &(ExecuteResponse::Deposit {
-            usd_deposit: Uint128::new(user_info.usd_deposit),
-            orai_deposit: Uint128::new(user_info.orai_deposit),
-            tier: new_tier,
-            status: ResponseStatus::Success,
-        })
-    )?;
+    config.tier_policy = tier_policy;
+    config.save(deps.storage)?;
 
-    Ok(Response::new().add_submessages(messages).set_data(answer))
+    Ok(Response::new().add_attribute("action", "changed tier policy"))
 }
 
-pub fn try_withdraw(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
-    let _env = env.clone();
-    let config = CONFIG_ITEM.load(deps.storage)?;
-    let contract_address = _env.contract.address;
-    config.assert_contract_active()?;
-
-    let sender = info.sender.to_string();
-
-    let min_tier = config.min_tier();
-    let user_info = USER_INFOS.may_load(deps.storage, sender)?.unwrap_or(state::UserInfo {
-        tier: min_tier,
-        ..Default::default()
-    });
+pub fn try_change_lock_boosts(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    lock_boosts: Vec<LockBoost>
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-    let mut amount: u128 = user_info.orai_deposit;
+    assert_lock_boosts(&lock_boosts)?;
 
-    // Consider the validator slashing
-    let mut total_delegated_with_slashing = 0;
-    let mut total_staked = 0;
+    config.lock_boosts = lock_boosts;
+    config.save(deps.storage)?;
 
-    // Get the total staked amount
-    let delegated_iterator: Vec<_> = USER_TOTAL_DELEGATED.range(
-        deps.storage,
-        None,
-        None,
-        cosmwasm_std::Order::Ascending
-    ).collect::<_>();
+    Ok(Response::new().add_attribute("action", "changed lock boosts"))
+}
 
-    for delegate in delegated_iterator {
-        let temp = delegate.unwrap();
-        total_staked += temp.1.u128();
+pub fn try_change_tier_validity(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tier_validity: Option<u64>
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
     }
 
-    //////////////////////////////////////////
+    if tier_validity == Some(0) {
+        return Err(ContractError::Std(StdError::generic_err("Tier validity should not be ZERO!")));
+    }
 
-    // Get total delegated amount from all validators considering with slashing
-    for validator in config.validators.clone() {
-        let current_delegate: Option<FullDelegation> = deps.querier
-            .query_delegation(contract_address.clone(), validator.clone().address)
-            .unwrap();
+    config.tier_validity = tier_validity;
+    config.save(deps.storage)?;
 
-        if let Some(full_delegation) = current_delegate {
-            let delegated_coin: Coin = full_delegation.amount;
-            let delegated_amount = delegated_coin.amount.u128();
-            total_delegated_with_slashing += delegated_amount;
-        } else {
-            let err_msg = format!("No delegation was found!");
+    Ok(Response::new().add_attribute("action", "changed tier validity"))
+}
 
-            return Err(ContractError::Std(StdError::generic_err(&err_msg)));
-        }
+/// Registers a contract that receives `tier_changed` messages.
+pub fn try_add_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    contract_addr: String
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
     }
 
-    amount = amount
-        .checked_mul(total_delegated_with_slashing)
-        .unwrap()
-        .checked_div(total_staked)
-        .unwrap();
-
-    USER_INFOS.remove(deps.storage, info.sender.to_string());
+    let contract_addr = deps.api.addr_validate(&contract_addr)?.to_string();
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if hooks.contains(&contract_addr) {
+        return Err(ContractError::Std(StdError::generic_err("Hook is already registered")));
+    }
 
-    let current_time = env.block.time.seconds();
-    let claim_time = MAX_UNIX_TIMESTAMP;
-    let withdrawal = UserWithdrawal {
-        amount,
-        timestamp: current_time,
-        claim_time,
-    };
+    hooks.push(contract_addr.clone());
+    HOOKS.save(deps.storage, &hooks)?;
 
-    let mut withdrawals = WITHDRAWALS_LIST.may_load(
-        deps.storage,
-        info.sender.to_string()
-    )?.unwrap_or_default();
+    Ok(
+        Response::new()
+            .add_attribute("action", "added hook")
+            .add_attribute("contract_addr", contract_addr)
+    )
+}
 
-    withdrawals.push(withdrawal);
-    WITHDRAWALS_LIST.save(deps.storage, info.sender.to_string(), &withdrawals)?;
+pub fn try_remove_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    contract_addr: String
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
 
-    let unbond_element = UserUnbond {
-        address: info.sender.into(),
-        amount,
-        timestamp: current_time,
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    let position = match hooks.iter().position(|hook| *hook == contract_addr) {
+        Some(position) => position,
+        None => {
+            return Err(ContractError::Std(StdError::generic_err("Hook not found")));
+        }
     };
 
-    let _ = UNBOND_LIST.push_back(deps.storage, &unbond_element);
-
-    // Batch Unbond whenever withdrawal happen
+    hooks.remove(position);
+    HOOKS.save(deps.storage, &hooks)?;
 
-    if UNBOND_LIST.len(deps.storage)? == 0 {
-        let err_msg = format!("Unbond List is Empty!");
-        return Err(ContractError::Std(StdError::generic_err(&err_msg)));
-    }
+    Ok(
+        Response::new()
+            .add_attribute("action", "removed hook")
+            .add_attribute("contract_addr", contract_addr)
+    )
+}
 
-    let mut first_unbond = UNBOND_LIST.front(deps.storage)?.unwrap();
+fn assert_lock_boosts(lock_boosts: &[LockBoost]) -> Result<(), ContractError> {
+    for (i, lock_boost) in lock_boosts.iter().enumerate() {
+        if lock_boost.duration == 0 || lock_boost.multiplier < 100 {
+            return Err(
+                ContractError::Std(
+                    StdError::generic_err(
+                        "Lock duration should not be ZERO and multiplier should be at least 100"
+                    )
+                )
+            );
+        }
 
-    if current_time - first_unbond.timestamp >= BATCH_PERIOD {
-        // Get the total undelegated amount, Pop valid unbond action from Deque, Update the claim times
-        let mut total_batch_undelegate_amount = 0;
+        if lock_boosts[..i].iter().any(|b| b.duration == lock_boost.duration) {
+            return Err(ContractError::Std(StdError::generic_err("Duplicated lock duration")));
+        }
+    }
 
-        while UNBOND_LIST.len(deps.storage).unwrap() > 0 {
-            first_unbond = UNBOND_LIST.front(deps.storage)?.unwrap();
-            total_batch_undelegate_amount += first_unbond.amount;
-            let key_address = first_unbond.address.to_string();
-            let mut withdrawals = WITHDRAWALS_LIST.may_load(
-                deps.storage,
-                key_address.clone()
-            )?.unwrap_or_default();
+    Ok(())
+}
 
-            // Calculate user's total delegated amount by subtracting undelegated amount
-            let mut user_total_delegated = USER_TOTAL_DELEGATED.may_load(
-                deps.storage,
-                key_address.to_string()
-            )?.unwrap_or_default();
+pub fn get_received_funds(storage: &dyn Storage, funds: &[Coin]) -> StdResult<Coin> {
+    let config = CONFIG_ITEM.load(storage)?;
+    config.assert_contract_active()?;
 
-            user_total_delegated = user_total_delegated
-                .checked_sub(Uint128::from(first_unbond.amount))
-                .unwrap();
+    match funds.first() {
+        None => {
+            return Err(StdError::generic_err("No Funds"));
+        }
+        Some(received) => {
+            /* Amount of tokens received cannot be zero */
+            if received.amount.is_zero() {
+                return Err(StdError::generic_err("Not Allow Zero Amount"));
+            }
 
-            USER_TOTAL_DELEGATED.save(
-                deps.storage,
-                key_address.to_string(),
-                &user_total_delegated
-            )?;
-            //////////////////////////////////////////
-
-            for i in 0..withdrawals.len() {
-                if
-                    withdrawals[i].claim_time == MAX_UNIX_TIMESTAMP &&
-                    withdrawals[i].amount == first_unbond.amount &&
-                    withdrawals[i].timestamp == first_unbond.timestamp
-                {
-                    withdrawals[i].claim_time = current_time.checked_add(UNBOUND_TIME).unwrap();
-                }
+            /* Allow to receive only token denomination defined
+            on contract instantiation "config.stable_denom" */
+            if received.denom.clone() != "orai" && config.stable_unit(&received.denom).is_none() {
+                return Err(StdError::generic_err("Unsopported token"));
             }
-            WITHDRAWALS_LIST.save(deps.storage, key_address.clone(), &withdrawals)?;
 
-            // Pop
-            UNBOND_LIST.pop_front(deps.storage)?;
+            /* Only one token can be received */
+            if funds.len() > 1 {
+                return Err(StdError::generic_err("Not Allowed Multiple Funds"));
+            }
+            Ok(received.clone())
         }
+    }
+}
 
-        let validators = config.validators;
-        let confirmed_amount = total_batch_undelegate_amount.checked_sub(4).unwrap();
+/// What a deposit of `funds` changes for `user_info`. Shared by `try_deposit` and the
+/// `SimulateDeposit` query, so the simulation fails and refunds exactly like the deposit.
+struct DepositPlan {
+    user_info: state::UserInfo,
+    oracle: Option<OraiPriceOracle>,
+    required_usd: u128,
+    required_amount: u128,
+    refund: u128,
+    usd_value: u128,
+}
 
-        let mut messages: Vec<SubMsg> = Vec::with_capacity(2);
+fn plan_deposit(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+    funds: &Coin,
+    mut user_info: state::UserInfo,
+    lock_duration: Option<u64>
+) -> StdResult<DepositPlan> {
+    let now = env.block.time.seconds();
+    let denom = funds.denom.clone();
+    let deposit_amount = funds.amount.u128();
+
+    // Stable coins count 1:1 in USD and stay in the contract, ORAI is priced by the oracle
+    let oracle = if denom == ORAI {
+        let oracle = OraiPriceOracle::new(deps, env)?;
+        oracle.check(deps.storage, config, now)?;
+        Some(oracle)
+    } else {
+        None
+    };
 
-        for validator in validators {
-            let weight_as_uint128 = Uint128::from(validator.weight);
+    let stable_unit = config.stable_unit(&denom).unwrap_or(OraiPriceOracle::ZERO_6);
+    let usd_value: u128 = match &oracle {
+        Some(oracle) => oracle.usd_amount(deposit_amount),
+        None => deposit_amount.checked_div(stable_unit).unwrap(),
+    };
 
-            // Perform the multiplication - Uint128 * Uint128
-            let multiplied = Uint128::from(confirmed_amount).multiply_ratio(
-                weight_as_uint128,
-                Uint128::from(100_u128)
-            );
+    user_info.end_lock(config, now);
+    // Unless tiers are sticky, the ORAI already deposited is valued at the current price
+    if let Some(oracle) = &oracle {
+        if config.tier_policy != (TierPolicy::Sticky {}) && user_info.orai_deposit != 0 {
+            let current_usd_deposit = oracle
+                .usd_amount(user_info.orai_deposit)
+                .checked_add(user_info.stable_usd_deposit(config))
+                .unwrap();
+            user_info.reprice(config, current_usd_deposit, now);
+        }
+    }
 
-            // Now, `multiplied` is Uint128, but we want the result as u128
-            let individual_amount: u128 = multiplied.u128();
+    if let Some(lock_duration) = lock_duration {
+        user_info.lock(config, lock_duration, now)?;
+    }
+
+    let current_tier = user_info.tier;
+    let old_usd_deposit = user_info.usd_deposit;
+    let new_usd_deposit = old_usd_deposit.checked_add(usd_value).unwrap();
+
+    let new_tier = config.tier_by_deposit(
+        Uint128::from(new_usd_deposit).multiply_ratio(user_info.boost(now), 100_u128).u128()
+    );
+
+    // A tier kept by the tier policy is not lowered by a deposit
+    if new_tier >= current_tier {
+        if current_tier == config.max_tier() {
+            return Err(StdError::generic_err("Reached max tier"));
+        }
+
+        let next_tier = current_tier.checked_sub(1).unwrap();
+        let next_tier_deposit: u128 = user_info.required_usd_deposit(
+            config.deposit_by_tier(next_tier),
+            now
+        );
+
+        let expected_deposit_usd = next_tier_deposit.saturating_sub(old_usd_deposit);
+
+        let err_msg = match &oracle {
+            Some(oracle) =>
+                format!(
+                    "You should deposit at least {} USD ({} ORAI)",
+                    expected_deposit_usd,
+                    oracle.orai_amount(expected_deposit_usd)
+                ),
+            None =>
+                format!(
+                    "You should deposit at least {} USD ({} {})",
+                    expected_deposit_usd,
+                    expected_deposit_usd.checked_mul(stable_unit).unwrap(),
+                    denom
+                ),
+        };
+
+        return Err(StdError::generic_err(&err_msg));
+    }
+
+    let new_tier_deposit = user_info.required_usd_deposit(config.deposit_by_tier(new_tier), now);
+
+    // A lock boost can make the deposit worth more than the new tier needs
+    let required_usd = new_tier_deposit.saturating_sub(old_usd_deposit);
+    let required_amount = match &oracle {
+        Some(oracle) => oracle.orai_amount(required_usd),
+        None => required_usd.checked_mul(stable_unit).unwrap(),
+    };
+    // The ORAI amount is rounded up, a deposit worth exactly the tier is taken whole
+    let required_amount = required_amount.min(deposit_amount);
+    let refund = deposit_amount - required_amount;
+
+    user_info.tier = new_tier;
+    user_info.timestamp = now;
+    user_info.usd_deposit = old_usd_deposit.checked_add(required_usd).unwrap();
+    user_info.downgrade_at = None;
+    user_info.renewed_at = now;
+
+    Ok(DepositPlan {
+        user_info,
+        oracle,
+        required_usd,
+        required_amount,
+        refund,
+        usd_value,
+    })
+}
+
+/// Deposits for `beneficiary`, the sender by default. With `funder_withdraws` only the
+/// sender can withdraw the deposit of the beneficiary.
+pub fn try_deposit(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    beneficiary: Option<String>,
+    funder_withdraws: bool,
+    lock_duration: Option<u64>
+) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let received_funds = get_received_funds(deps.storage, &info.funds)?;
+    let denom = received_funds.denom.clone();
+
+    let sender = info.sender.to_string();
+    let address = match beneficiary {
+        Some(beneficiary) => deps.api.addr_validate(&beneficiary)?.to_string(),
+        None => sender.clone(),
+    };
+    let withdrawer = if funder_withdraws && address != sender {
+        Some(sender.clone())
+    } else {
+        None
+    };
+    let min_tier = config.min_tier();
+
+    // A linked address shows the tier of its owner, its own deposit would be hidden
+    if let Some(link) = LINKED_OWNERS.may_load(deps.storage, address.clone())? {
+        return Err(
+            ContractError::Std(
+                StdError::generic_err(format!("{} is linked to {}", address, link.owner))
+            )
+        );
+    }
+
+    let user_info = USER_INFOS.may_load(deps.storage, address.clone())?;
+    match &user_info {
+        Some(user_info) if user_info.withdrawer != withdrawer => {
+            return Err(
+                ContractError::Std(
+                    StdError::generic_err(
+                        format!("The deposit of {} is withdrawn by another address", address)
+                    )
+                )
+            );
+        }
+        // The beneficiary has to approve the funder to withdraw before the funder can keep it
+        None if withdrawer.is_some() => {
+            assert_permission(deps.storage, &env, &address, &sender, Permission::Withdraw)?;
+        }
+        _ => {}
+    }
+
+    // Locking keeps the funds from the withdrawer, so nobody else may do it
+    if lock_duration.is_some() && withdrawer.as_deref().unwrap_or(&address) != sender {
+        return Err(
+            ContractError::Std(StdError::generic_err("Only the withdrawer can lock the deposit"))
+        );
+    }
+
+    let user_info = user_info.unwrap_or(state::UserInfo {
+        tier: min_tier,
+        withdrawer,
+        ..Default::default()
+    });
+    let DepositPlan { mut user_info, oracle, required_usd, required_amount, refund, .. } =
+        plan_deposit(deps.as_ref(), &env, &config, &received_funds, user_info, lock_duration)?;
+    let new_tier = user_info.tier;
+    let now = env.block.time.seconds();
+
+    // Accepting the quote moves the circuit breaker baseline
+    if let Some(oracle) = &oracle {
+        oracle.accept(deps.storage, &config, now)?;
+    }
+
+    let mut messages: Vec<SubMsg> = Vec::with_capacity(2);
+    if refund != 0 {
+        let send_msg = BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: coins(refund, denom.clone()),
+        };
+
+        let msg = CosmosMsg::Bank(send_msg);
+        messages.push(SubMsg::new(msg));
+    }
+
+    let mut lifetime_deposits = LIFETIME_DEPOSITS.may_load(
+        deps.storage,
+        address.clone()
+    )?.unwrap_or_default();
+    lifetime_deposits.add(&denom, required_amount, required_usd);
+    LIFETIME_DEPOSITS.save(deps.storage, address.clone(), &lifetime_deposits)?;
+
+    if address != sender {
+        let mut funded_deposit = FUNDED_DEPOSITS.may_load(deps.storage, (
+            sender.clone(),
+            address.clone(),
+        ))?.unwrap_or_default();
+        funded_deposit.add(&denom, required_amount, required_usd, funder_withdraws, now);
+        FUNDED_DEPOSITS.save(deps.storage, (sender.clone(), address.clone()), &funded_deposit)?;
+        DEPOSIT_FUNDERS.save(deps.storage, (address.clone(), sender.clone()), &())?;
+    }
+
+    if oracle.is_none() {
+        user_info.add_stable_deposit(&denom, required_amount);
+        messages.extend(save_user_info(deps.storage, &config, &address, &user_info, &env)?);
+
+        let answer = to_json_binary(
+            &(ExecuteResponse::Deposit {
+                usd_deposit: Uint128::new(user_info.usd_deposit),
+                orai_deposit: Uint128::new(user_info.orai_deposit),
+                tier: new_tier,
+                status: ResponseStatus::Success,
+            })
+        )?;
+
+        return Ok(Response::new().add_submessages(messages).set_data(answer));
+    }
+
+    let orai_deposit = required_amount;
+    user_info.orai_deposit = user_info.orai_deposit.checked_add(orai_deposit).unwrap();
+
+    // New shares are priced after the slashing that already happened and do not take
+    // the rewards earned before them
+    sync_slashing(&mut deps, &env, &config)?;
+    let (harvest_messages, _, _) = harvest(&mut deps, &env, &config)?;
+    messages.extend(harvest_messages);
+    let mut delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+    let shares = delegation_state.mint(orai_deposit);
+    user_info.shares = user_info.shares.checked_add(shares).unwrap();
+    DELEGATION_STATE.save(deps.storage, &delegation_state)?;
+
+    // Calculate user's total delegated amount, it belongs to whoever withdraws it
+    let withdrawer = user_info.withdrawer_or(&address);
+    let mut user_total_delegated = USER_TOTAL_DELEGATED.may_load(
+        deps.storage,
+        withdrawer.clone()
+    )?.unwrap_or_default();
+
+    user_total_delegated = user_total_delegated.checked_add(Uint128::from(orai_deposit)).unwrap();
+
+    USER_TOTAL_DELEGATED.save(deps.storage, withdrawer, &user_total_delegated)?;
+    //////////////////////////////////////////
+
+    messages.extend(save_user_info(deps.storage, &config, &address, &user_info, &env)?);
+
+    messages.extend(delegate_by_weights(&config.validators, orai_deposit));
+
+    let answer = to_json_binary(
+        &(ExecuteResponse::Deposit {
+            usd_deposit: Uint128::new(user_info.usd_deposit),
+            orai_deposit: Uint128::new(user_info.orai_deposit),
+            tier: new_tier,
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    Ok(Response::new().add_submessages(messages).set_data(answer))
+}
+
+/// Splits `amount` by the validator weights. The rounding remainder goes to the heaviest
+/// validator, so everything is delegated and not mistaken for slashing later.
+fn delegate_by_weights(validators: &[ValidatorWithWeight], amount: u128) -> Vec<SubMsg> {
+    let mut messages: Vec<SubMsg> = Vec::with_capacity(validators.len());
+
+    let split: u128 = validators
+        .iter()
+        .map(|v| (amount * v.weight) / 100)
+        .sum();
+    let heaviest = validators.iter().max_by_key(|v| v.weight).map(|v| v.address.clone());
+
+    for validator in validators {
+        let mut individual_amount = (amount * validator.weight) / 100;
+        if Some(&validator.address) == heaviest.as_ref() {
+            individual_amount += amount - split;
+        }
+        if individual_amount == 0 {
+            continue;
+        }
+
+        let delegate_msg = StakingMsg::Delegate {
+            validator: validator.address.clone(),
+            amount: coin(individual_amount, ORAI),
+        };
+
+        let msg: CosmosMsg = CosmosMsg::Staking(delegate_msg);
+        messages.push(SubMsg::new(msg));
+    }
+
+    messages
+}
+
+/// Stores the user info and checkpoints its tier for the `TierAt` query.
+/// Saves `user_info` and returns the hook notifications if its tier changed.
+fn save_user_info(
+    storage: &mut dyn Storage,
+    config: &Config,
+    address: &str,
+    user_info: &state::UserInfo,
+    env: &Env
+) -> StdResult<Vec<SubMsg>> {
+    let old_info = USER_INFOS.may_load(storage, address.to_string())?;
+    let old_tier = old_info.as_ref().map_or(config.min_tier(), |old_info| old_info.tier);
+    USER_INFOS.save(storage, address.to_string(), user_info)?;
+    update_tier_stats(storage, address, old_info.as_ref(), Some(user_info))?;
+
+    // The tier is recorded for the address that holds it, a linked one while linked
+    let holder = tier_holder(storage, address)?;
+    checkpoint_tier(storage, config, &holder, user_info.checkpoint(config), env)?;
+
+    tier_hook_messages(storage, &holder, old_tier, user_info.tier)
+}
+
+fn remove_user_info(
+    storage: &mut dyn Storage,
+    config: &Config,
+    address: &str,
+    env: &Env
+) -> StdResult<Vec<SubMsg>> {
+    let old_info = USER_INFOS.may_load(storage, address.to_string())?;
+    let old_tier = old_info.as_ref().map_or(config.min_tier(), |old_info| old_info.tier);
+    USER_INFOS.remove(storage, address.to_string());
+    update_tier_stats(storage, address, old_info.as_ref(), None)?;
+
+    // The funded deposits went out with the position
+    let funders = DEPOSIT_FUNDERS.prefix(address.to_string())
+        .keys(storage, None, None, cosmwasm_std::Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    for funder in funders {
+        FUNDED_DEPOSITS.remove(storage, (funder.clone(), address.to_string()));
+        DEPOSIT_FUNDERS.remove(storage, (address.to_string(), funder));
+    }
+
+    let holder = tier_holder(storage, address)?;
+    checkpoint_tier(storage, config, &holder, TierCheckpoint::minimal(config), env)?;
+
+    tier_hook_messages(storage, &holder, old_tier, config.min_tier())
+}
+
+/// Address that holds the tier of `address`. A tier owner lends it to the linked address,
+/// so the tier never counts for both.
+fn tier_holder(storage: &dyn Storage, address: &str) -> StdResult<String> {
+    Ok(LINKS.may_load(storage, address.to_string())?.unwrap_or(address.to_string()))
+}
+
+/// Moves the tier of `owner` between the owner and `linked`, in the tier history and for
+/// the hooks.
+fn move_tier(
+    storage: &mut dyn Storage,
+    config: &Config,
+    owner: &str,
+    linked: &str,
+    to_linked: bool,
+    env: &Env
+) -> StdResult<Vec<SubMsg>> {
+    let user_info = USER_INFOS.may_load(storage, owner.to_string())?;
+    let checkpoint = user_info.as_ref().map_or(TierCheckpoint::minimal(config), |user_info| {
+        user_info.checkpoint(config)
+    });
+    let tier = checkpoint.tier;
+    let (from, to) = if to_linked { (owner, linked) } else { (linked, owner) };
+
+    checkpoint_tier(storage, config, from, TierCheckpoint::minimal(config), env)?;
+    checkpoint_tier(storage, config, to, checkpoint, env)?;
+
+    let mut messages = tier_hook_messages(storage, from, tier, config.min_tier())?;
+    messages.extend(tier_hook_messages(storage, to, config.min_tier(), tier)?);
+    Ok(messages)
+}
+
+/// Moves the position of `address` between the tier index and the running tier totals.
+fn update_tier_stats(
+    storage: &mut dyn Storage,
+    address: &str,
+    old_info: Option<&state::UserInfo>,
+    new_info: Option<&state::UserInfo>
+) -> StdResult<()> {
+    if let Some(old_info) = old_info {
+        let mut tier_stats = TIER_STATS.may_load(storage, old_info.tier)?.unwrap_or_default();
+        tier_stats.sub(old_info);
+        TIER_STATS.save(storage, old_info.tier, &tier_stats)?;
+        TIER_HOLDERS.remove(storage, (old_info.tier, address.to_string()));
+    }
+
+    if let Some(new_info) = new_info {
+        let mut tier_stats = TIER_STATS.may_load(storage, new_info.tier)?.unwrap_or_default();
+        tier_stats.add(new_info);
+        TIER_STATS.save(storage, new_info.tier, &tier_stats)?;
+        TIER_HOLDERS.save(storage, (new_info.tier, address.to_string()), &())?;
+    }
+
+    Ok(())
+}
+
+fn tier_hook_messages(
+    storage: &dyn Storage,
+    address: &str,
+    old_tier: u8,
+    new_tier: u8
+) -> StdResult<Vec<SubMsg>> {
+    if old_tier == new_tier {
+        return Ok(Vec::new());
+    }
+
+    let hook_msg = to_json_binary(
+        &(TierHookMsg::TierChanged {
+            address: address.to_string(),
+            old_tier,
+            new_tier,
+        })
+    )?;
+
+    let hooks = HOOKS.may_load(storage)?.unwrap_or_default();
+    let messages = hooks
+        .into_iter()
+        .map(|contract_addr| {
+            let execute_msg = WasmMsg::Execute {
+                contract_addr,
+                msg: hook_msg.clone(),
+                funds: vec![],
+            };
+            // A failing hook is reported in `reply`, it never reverts the action
+            SubMsg::reply_on_error(CosmosMsg::Wasm(execute_msg), HOOK_REPLY_ID).with_gas_limit(
+                HOOK_GAS_LIMIT
+            )
+        })
+        .collect();
+
+    Ok(messages)
+}
+
+fn checkpoint_tier(
+    storage: &mut dyn Storage,
+    config: &Config,
+    address: &str,
+    checkpoint: TierCheckpoint,
+    env: &Env
+) -> StdResult<()> {
+    let now = env.block.time.seconds();
+    let last_checkpoint = last_checkpoint(storage, address, now)?.unwrap_or(
+        TierCheckpoint::minimal(config)
+    );
+    if last_checkpoint == checkpoint {
+        return Ok(());
+    }
+
+    TIER_HISTORY.save(storage, (address.to_string(), now), &checkpoint)
+}
+
+fn last_checkpoint(
+    storage: &dyn Storage,
+    address: &str,
+    timestamp: u64
+) -> StdResult<Option<TierCheckpoint>> {
+    let checkpoint = TIER_HISTORY.prefix(address.to_string())
+        .range(
+            storage,
+            None,
+            Some(Bound::inclusive(timestamp)),
+            cosmwasm_std::Order::Descending
+        )
+        .next()
+        .transpose()?;
+
+    Ok(checkpoint.map(|(_, checkpoint)| checkpoint))
+}
+
+/// Returns the tier `address` held at `timestamp`, the minimal one before its first deposit
+/// or once the tier expired.
+pub fn tier_at(
+    storage: &dyn Storage,
+    config: &Config,
+    address: &str,
+    timestamp: u64
+) -> StdResult<u8> {
+    let tier = match last_checkpoint(storage, address, timestamp)? {
+        Some(checkpoint) if !config.tier_expired(checkpoint.renewed_at, timestamp) => {
+            checkpoint.tier_at(timestamp)
+        }
+        _ => config.min_tier(),
+    };
+
+    Ok(tier)
+}
+
+/// Returns the worst tier `address` held during the whole of `[start, end]`, the minimal one
+/// when no checkpoint reaches back to `start`.
+pub fn tier_held(
+    storage: &dyn Storage,
+    config: &Config,
+    address: &str,
+    start: u64,
+    end: u64
+) -> StdResult<u8> {
+    let first = match last_checkpoint(storage, address, start)? {
+        Some(checkpoint) => (start, checkpoint),
+        None => {
+            return Ok(config.min_tier());
+        }
+    };
+
+    // The tier changes with a checkpoint, when a lock ends and when the tier expires
+    let checkpoints = TIER_HISTORY.prefix(address.to_string())
+        .range(
+            storage,
+            Some(Bound::exclusive(start)),
+            Some(Bound::inclusive(end)),
+            cosmwasm_std::Order::Ascending
+        )
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut worst_tier = tier_at(storage, config, address, start)?;
+    for (timestamp, checkpoint) in std::iter::once(first).chain(checkpoints) {
+        let expires_at = config.tier_validity.map(|v| checkpoint.renewed_at.saturating_add(v));
+        let changes = [Some(timestamp), Some(checkpoint.lock_end), expires_at];
+        for change in changes.into_iter().flatten() {
+            if change >= start && change <= end {
+                worst_tier = worst_tier.max(tier_at(storage, config, address, change)?);
+            }
+        }
+    }
+
+    Ok(worst_tier)
+}
+
+/// Withdraws part of the ORAI deposit and moves the user to the tier that the remaining
+/// deposit gives. Stable coin deposits are left untouched. Returns the shares to burn.
+fn partial_withdrawal(
+    config: &Config,
+    mut user_info: state::UserInfo,
+    withdraw_amount: Option<Uint128>,
+    target_tier: Option<u8>
+) -> Result<(u128, state::UserInfo), ContractError> {
+    if user_info.orai_deposit == 0 {
+        return Err(ContractError::Std(StdError::generic_err("Nothing to withdraw")));
+    }
+
+    let stable_usd = user_info.stable_usd_deposit(config);
+    let orai_usd = match user_info.usd_deposit.checked_sub(stable_usd) {
+        Some(orai_usd) => orai_usd,
+        None => {
+            return Err(
+                ContractError::Std(
+                    StdError::generic_err("USD deposit is lower than the stable coin deposits")
+                )
+            );
+        }
+    };
+
+    let (orai_amount, usd_amount) = match (withdraw_amount, target_tier) {
+        (Some(withdraw_amount), None) => {
+            let orai_amount = withdraw_amount.u128();
+            if orai_amount == 0 || orai_amount > user_info.orai_deposit {
+                return Err(
+                    ContractError::Std(
+                        StdError::generic_err("Withdraw amount must be from 1 to the ORAI deposit")
+                    )
+                );
+            }
+
+            let usd_amount = Uint128::from(orai_usd)
+                .multiply_ratio(orai_amount, user_info.orai_deposit)
+                .u128();
+            (orai_amount, usd_amount)
+        }
+        (None, Some(target_tier)) => {
+            if target_tier <= user_info.tier || target_tier > config.min_tier() {
+                return Err(
+                    ContractError::Std(
+                        StdError::generic_err("Target tier must be lower than the current one")
+                    )
+                );
+            }
+
+            let target_usd = if target_tier == config.min_tier() {
+                0
+            } else {
+                config.deposit_by_tier(target_tier)
+            };
+            // Stable coins are only returned by a full withdrawal, they keep their tier
+            if config.tier_by_deposit(stable_usd) < target_tier {
+                return Err(
+                    ContractError::Std(
+                        StdError::generic_err(
+                            "Stable deposits already cover a higher tier than the target tier"
+                        )
+                    )
+                );
+            }
+
+            let keep_usd = target_usd.saturating_sub(stable_usd);
+            if keep_usd > orai_usd {
+                return Err(
+                    ContractError::Std(
+                        StdError::generic_err("The deposit is worth less than the target tier")
+                    )
+                );
+            }
+
+            // Nothing to keep also covers an ORAI deposit that is worth no USD anymore
+            let keep_orai = if keep_usd == 0 {
+                0
+            } else {
+                Uint128::from(user_info.orai_deposit).multiply_ratio(keep_usd, orai_usd).u128()
+            };
+
+            (
+                user_info.orai_deposit.checked_sub(keep_orai).unwrap(),
+                orai_usd.checked_sub(keep_usd).unwrap(),
+            )
+        }
+        _ => {
+            return Err(
+                ContractError::Std(StdError::generic_err("Specify either amount or target tier"))
+            );
+        }
+    };
+
+    let shares = Uint128::from(user_info.shares)
+        .multiply_ratio(orai_amount, user_info.orai_deposit)
+        .u128();
+
+    user_info.shares = user_info.shares.checked_sub(shares).unwrap();
+    user_info.orai_deposit = user_info.orai_deposit.checked_sub(orai_amount).unwrap();
+    user_info.usd_deposit = user_info.usd_deposit.checked_sub(usd_amount).unwrap();
+    user_info.tier = config.tier_by_deposit(user_info.usd_deposit);
+
+    if let Some(target_tier) = target_tier {
+        if user_info.tier != target_tier {
+            return Err(
+                ContractError::Std(
+                    StdError::generic_err("Target tier cannot be reached by withdrawing ORAI only")
+                )
+            );
+        }
+    }
+
+    Ok((shares, user_info))
+}
+
+pub fn try_withdraw(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    withdraw_amount: Option<Uint128>,
+    target_tier: Option<u8>,
+    owner: Option<String>
+) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let sender = info.sender.to_string();
+    let owner = owner.unwrap_or(sender.clone());
+
+    let min_tier = config.min_tier();
+    let mut user_info = USER_INFOS.may_load(deps.storage, owner.clone())?.unwrap_or(
+        state::UserInfo {
+            tier: min_tier,
+            ..Default::default()
+        }
+    );
+
+    // Funds always go back to the withdrawer, the funder of the deposit if it kept that right
+    let withdrawer = user_info.withdrawer_or(&owner);
+    assert_permission(deps.storage, &env, &withdrawer, &sender, Permission::Withdraw)?;
+    let sender = withdrawer;
+
+    if user_info.lock_end > env.block.time.seconds() {
+        return Err(
+            ContractError::Std(
+                StdError::generic_err(format!("Deposit is locked until {}", user_info.lock_end))
+            )
+        );
+    }
+
+    user_info.end_lock(&config, env.block.time.seconds());
+
+    // Burned shares take their part of the slashing, it is not left to the remaining holders,
+    // and the rewards earned until now are settled before the shares are gone
+    sync_slashing(&mut deps, &env, &config)?;
+    let (mut messages, _, _) = harvest(&mut deps, &env, &config)?;
+    let delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+
+    if withdraw_amount.is_some() || target_tier.is_some() {
+        let (shares, remaining_info) = partial_withdrawal(
+            &config,
+            user_info,
+            withdraw_amount,
+            target_tier
+        )?;
+
+        // Nothing is left to hold the tier, behave like a full withdrawal
+        if remaining_info.orai_deposit == 0 && remaining_info.stable_deposits.is_empty() {
+            if remaining_info.pending_rewards != 0 {
+                let send_msg = BankMsg::Send {
+                    to_address: sender.clone(),
+                    amount: coins(remaining_info.pending_rewards, ORAI),
+                };
+                messages.push(SubMsg::new(CosmosMsg::Bank(send_msg)));
+            }
+
+            messages.extend(remove_user_info(deps.storage, &config, &owner, &env)?);
+        } else {
+            messages.extend(save_user_info(deps.storage, &config, &owner, &remaining_info, &env)?);
+        }
+
+        return queue_withdrawal(deps, env, sender, shares, messages);
+    }
+
+    // Stable coin deposits were never delegated, return them right away
+    for stable_deposit in user_info.stable_deposits.iter() {
+        let send_msg = BankMsg::Send {
+            to_address: sender.clone(),
+            amount: vec![stable_deposit.clone()],
+        };
+        messages.push(SubMsg::new(CosmosMsg::Bank(send_msg)));
+    }
+
+    // The position is closed, rewards that were not claimed yet go out with it
+    if user_info.pending_rewards != 0 {
+        let send_msg = BankMsg::Send {
+            to_address: sender.clone(),
+            amount: coins(user_info.pending_rewards, ORAI),
+        };
+        messages.push(SubMsg::new(CosmosMsg::Bank(send_msg)));
+    }
+
+    if user_info.orai_deposit == 0 {
+        if messages.is_empty() {
+            return Err(ContractError::Std(StdError::generic_err("Nothing to withdraw")));
+        }
+
+        messages.extend(remove_user_info(deps.storage, &config, &owner, &env)?);
+
+        let answer = to_json_binary(
+            &(ExecuteResponse::Withdraw {
+                status: ResponseStatus::Success,
+            })
+        )?;
+        return Ok(
+            Response::new()
+                .add_submessages(messages)
+                .set_data(answer)
+                .add_attribute("action", "Stable deposits returned!")
+        );
+    }
+
+    messages.extend(remove_user_info(deps.storage, &config, &owner, &env)?);
+
+    queue_withdrawal(deps, env, sender, user_info.shares, messages)
+}
+
+/// Burns `shares` and adds their ORAI value to the current unbonding epoch.
+fn queue_withdrawal(
+    deps: DepsMut,
+    env: Env,
+    sender: String,
+    shares: u128,
+    messages: Vec<SubMsg>
+) -> Result<Response, ContractError> {
+    // The share price already accounts for the validator slashing
+    let mut delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    let amount = delegation_state.burn(shares);
+    DELEGATION_STATE.save(deps.storage, &delegation_state)?;
+
+    let current_time = env.block.time.seconds();
+    let epoch_id = UNBOND_EPOCH.may_load(deps.storage)?.unwrap_or_default();
+
+    let mut epoch = UNBOND_EPOCHS.may_load(deps.storage, epoch_id)?.unwrap_or(UnbondEpoch {
+        start_time: current_time,
+        ..Default::default()
+    });
+    epoch.amount = epoch.amount.checked_add(amount).unwrap();
+    UNBOND_EPOCHS.save(deps.storage, epoch_id, &epoch)?;
+
+    let withdrawal = UserWithdrawal {
+        amount,
+        timestamp: current_time,
+        epoch_id,
+    };
+
+    let mut withdrawals = WITHDRAWALS_LIST.may_load(
+        deps.storage,
+        sender.clone()
+    )?.unwrap_or_default();
+
+    withdrawals.push(withdrawal);
+    WITHDRAWALS_LIST.save(deps.storage, sender.clone(), &withdrawals)?;
+
+    // Repeated withdrawals of one user in the same epoch share one entry
+    let mut unbond_element = EPOCH_UNBONDS.may_load(deps.storage, (
+        epoch_id,
+        sender.clone(),
+    ))?.unwrap_or(UserUnbond {
+        address: sender.clone(),
+        amount: 0,
+        timestamp: current_time,
+    });
+    unbond_element.amount = unbond_element.amount.checked_add(amount).unwrap();
+    unbond_element.timestamp = current_time;
+    EPOCH_UNBONDS.save(deps.storage, (epoch_id, sender), &unbond_element)?;
+
+    let answer = to_json_binary(
+        &(ExecuteResponse::Withdraw {
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    Ok(
+        Response::new()
+            .add_submessages(messages)
+            .set_data(answer)
+            .add_attribute("action", "Add to withdraw list!")
+    )
+}
+
+/// Compares the actual delegations with the stored totals. A loss is taken from the
+/// share price only, the amounts already queued for unbonding keep their value so the
+/// contract stays able to pay every claim.
+fn sync_slashing(deps: &mut DepsMut, env: &Env, config: &Config) -> StdResult<()> {
+    let mut delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+
+    let slashed = slashed_amount(deps.as_ref(), env, config, &delegation_state)?;
+    if slashed != 0 {
+        delegation_state.total_delegated = delegation_state.total_delegated.saturating_sub(slashed);
+        DELEGATION_STATE.save(deps.storage, &delegation_state)?;
+    }
+
+    Ok(())
+}
+
+/// ORAI lost to slashing that the stored totals do not reflect yet.
+fn slashed_amount(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+    delegation_state: &DelegationState
+) -> StdResult<u128> {
+    let mut total_delegated_with_slashing: u128 = 0;
+    for validator in config.validators.iter() {
+        let current_delegate: Option<FullDelegation> = deps.querier.query_delegation(
+            env.contract.address.clone(),
+            validator.address.clone()
+        )?;
+
+        if let Some(full_delegation) = current_delegate {
+            total_delegated_with_slashing += full_delegation.amount.amount.u128();
+        }
+    }
+
+    let expected = delegation_state.total_delegated
+        .checked_add(delegation_state.total_queued)
+        .unwrap();
+    Ok(expected.saturating_sub(total_delegated_with_slashing))
+}
+
+/// Closes the current unbonding epoch, undelegates its whole amount at once and opens
+/// the next epoch. The epoch entries are processed later by `process_unbond_batch`.
+fn close_unbond_epoch(
+    deps: &mut DepsMut,
+    env: &Env,
+    config: &Config
+) -> Result<Vec<SubMsg>, ContractError> {
+    let current_time = env.block.time.seconds();
+    let epoch_id = UNBOND_EPOCH.may_load(deps.storage)?.unwrap_or_default();
+
+    let mut epoch = match UNBOND_EPOCHS.may_load(deps.storage, epoch_id)? {
+        Some(epoch) if epoch.amount > 0 => epoch,
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err("Unbond List is Empty!")));
+        }
+    };
+
+    if current_time - epoch.start_time < BATCH_PERIOD {
+        return Err(
+            ContractError::Std(StdError::generic_err("The time difference is lower than 5 days!"))
+        );
+    }
+
+    sync_slashing(deps, env, config)?;
+    let (mut messages, _, _) = harvest(deps, env, config)?;
+
+    PENDING_BATCH.save(deps.storage, &(PendingBatch {
+        epoch_id,
+        last_address: None,
+    }))?;
+
+    epoch.close_time = Some(current_time);
+    epoch.claim_time = Some(current_time.checked_add(UNBOUND_TIME).unwrap());
+    UNBOND_EPOCHS.save(deps.storage, epoch_id, &epoch)?;
+    UNBOND_EPOCH.save(deps.storage, &epoch_id.checked_add(1).unwrap())?;
+
+    let mut delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    delegation_state.total_queued = delegation_state.total_queued.saturating_sub(epoch.amount);
+    DELEGATION_STATE.save(deps.storage, &delegation_state)?;
+
+    // Undelegate where the stake actually is, the weights may not have been applied yet
+    let delegations = deps.querier.query_all_delegations(env.contract.address.clone())?;
+    let total_delegated: u128 = delegations
+        .iter()
+        .map(|d| d.amount.amount.u128())
+        .sum();
+    let confirmed_amount = epoch.amount.saturating_sub(4).min(total_delegated);
+
+    let mut amounts: Vec<(String, u128, u128)> = delegations
+        .into_iter()
+        .map(|d| {
+            let delegated = d.amount.amount.u128();
+            let individual_amount = Uint128::from(confirmed_amount)
+                .multiply_ratio(delegated, total_delegated)
+                .u128();
+            (d.validator, delegated, individual_amount)
+        })
+        .collect();
 
-            let undelegate_msg = StakingMsg::Undelegate {
-                validator: validator.address,
-                amount: coin(individual_amount, ORAI),
-            };
-            let msg = CosmosMsg::Staking(undelegate_msg);
-            messages.push(SubMsg::new(msg));
+    // The rounding remainder is taken from the largest delegation
+    let split: u128 = amounts
+        .iter()
+        .map(|(_, _, amount)| amount)
+        .sum();
+    if let Some(largest) = amounts.iter_mut().max_by_key(|(_, delegated, _)| *delegated) {
+        largest.2 = largest.2.checked_add(confirmed_amount - split).unwrap().min(largest.1);
+    }
+
+    for (validator, _, individual_amount) in amounts {
+        if individual_amount == 0 {
+            continue;
         }
 
-        let answer = to_json_binary(
-            &(ExecuteResponse::Withdraw {
-                status: ResponseStatus::Success,
-            })
-        )?;
-        Ok(
-            Response::new()
-                .add_submessages(messages)
-                .set_data(answer)
-                .add_attribute("action", "Add to withdraw list and batch unbond done!")
-        )
+        let undelegate_msg = StakingMsg::Undelegate {
+            validator,
+            amount: coin(individual_amount, ORAI),
+        };
+        let msg = CosmosMsg::Staking(undelegate_msg);
+        messages.push(SubMsg::new(msg));
+    }
+
+    Ok(messages)
+}
+
+/// Processes up to `limit` entries of the closed epoch. Returns `true` when the epoch is done.
+fn process_unbond_batch(storage: &mut dyn Storage, limit: usize) -> StdResult<bool> {
+    let mut pending_batch = match PENDING_BATCH.may_load(storage)? {
+        Some(pending_batch) => pending_batch,
+        None => {
+            return Ok(true);
+        }
+    };
+
+    let min = pending_batch.last_address.clone().map(Bound::exclusive);
+    let unbonds = EPOCH_UNBONDS.prefix(pending_batch.epoch_id)
+        .range(storage, min, None, cosmwasm_std::Order::Ascending)
+        .take(limit.checked_add(1).unwrap())
+        .collect::<StdResult<Vec<_>>>()?;
+    let finished = unbonds.len() <= limit;
+
+    for (key_address, unbond) in unbonds.into_iter().take(limit) {
+        // Calculate user's total delegated amount by subtracting undelegated amount
+        let mut user_total_delegated = USER_TOTAL_DELEGATED.may_load(
+            storage,
+            key_address.to_string()
+        )?.unwrap_or_default();
+
+        user_total_delegated = user_total_delegated.saturating_sub(Uint128::from(unbond.amount));
+
+        USER_TOTAL_DELEGATED.save(storage, key_address.to_string(), &user_total_delegated)?;
+        pending_batch.last_address = Some(key_address);
+    }
+
+    if finished {
+        PENDING_BATCH.remove(storage);
     } else {
-        Ok(Response::new().add_attribute("action", "Add to withdraw list!"))
+        PENDING_BATCH.save(storage, &pending_batch)?;
     }
+
+    Ok(finished)
 }
 
-pub fn try_batch_unbond(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
-    if UNBOND_LIST.len(deps.storage)? == 0 {
-        let err_msg = format!("Unbond List is Empty!");
-        return Err(ContractError::Std(StdError::generic_err(&err_msg)));
+pub fn try_batch_unbond(
+    mut deps: DepsMut,
+    env: Env,
+    limit: Option<u32>
+) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let limit = limit.unwrap_or(50) as usize;
+    if limit == 0 {
+        return Err(ContractError::Std(StdError::generic_err("Limit should not be ZERO!")));
+    }
+
+    // Finish the previous batch before a new epoch can be closed
+    let mut messages: Vec<SubMsg> = Vec::new();
+    if PENDING_BATCH.may_load(deps.storage)?.is_none() {
+        messages = close_unbond_epoch(&mut deps, &env, &config)?;
     }
 
+    let finished = process_unbond_batch(deps.storage, limit)?;
+
+    let answer = to_json_binary(
+        &(ExecuteResponse::Withdraw {
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    Ok(
+        Response::new()
+            .add_submessages(messages)
+            .set_data(answer)
+            .add_attribute("batch_finished", finished.to_string())
+    )
+}
+
+pub fn try_claim(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+    start: Option<u32>,
+    limit: Option<u32>,
+    owner: Option<String>
+) -> Result<Response, ContractError> {
     let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let sender = owner.unwrap_or(info.sender.to_string());
+    let recipient = claim_recipient(
+        deps.storage,
+        &env,
+        &sender,
+        info.sender.as_str(),
+        recipient,
+        Permission::Claim
+    )?;
+
+    let mut withdrawals: Vec<UserWithdrawal> = WITHDRAWALS_LIST.may_load(
+        deps.storage,
+        sender.clone()
+    )?.unwrap_or_default();
+
+    let length = withdrawals.len();
+
+    if length == 0 {
+        return Err(ContractError::Std(StdError::generic_err("Nothing to claim")));
+    }
+
+    let start: usize = start.unwrap_or(0) as usize;
+    let limit = limit.unwrap_or(50) as usize;
+    let withdrawals_iter: std::iter::Take<
+        std::iter::Skip<std::slice::Iter<'_, UserWithdrawal>>
+    > = withdrawals.iter().skip(start).take(limit);
+
     let current_time = env.block.time.seconds();
+    let mut remove_indices = Vec::new();
+    let mut claim_amount = 0u128;
+
+    for (index, withdrawal) in withdrawals_iter.enumerate() {
+        let claim_time = epoch_claim_time(deps.storage, withdrawal.epoch_id)?;
 
-    let mut first_unbond = UNBOND_LIST.front(deps.storage)?.unwrap();
+        if current_time >= claim_time {
+            remove_indices.push(index.checked_add(start).unwrap());
+            claim_amount = claim_amount.checked_add(withdrawal.amount).unwrap();
 
-    if current_time - first_unbond.timestamp < BATCH_PERIOD {
-        let err_msg = format!("The time difference is lower than 5 days!");
-        return Err(ContractError::Std(StdError::generic_err(&err_msg)));
+            let mut epoch = UNBOND_EPOCHS.load(deps.storage, withdrawal.epoch_id)?;
+            epoch.claimed = epoch.claimed.checked_add(withdrawal.amount).unwrap();
+            UNBOND_EPOCHS.save(deps.storage, withdrawal.epoch_id, &epoch)?;
+        }
     }
 
-    // Get the total undelegated amount, Pop valid unbond action from Deque, Update the claim times
-    let mut total_batch_undelegate_amount = 0;
+    if claim_amount == 0 {
+        return Err(ContractError::Std(StdError::generic_err("Nothing to claim")));
+    }
 
-    while UNBOND_LIST.len(deps.storage).unwrap() > 0 {
-        first_unbond = UNBOND_LIST.front(deps.storage)?.unwrap();
-        total_batch_undelegate_amount += first_unbond.amount;
-        let key_address = first_unbond.address.to_string();
-        let mut withdrawals = WITHDRAWALS_LIST.may_load(
-            deps.storage,
-            key_address.clone()
-        )?.unwrap_or_default();
+    for (shift, index) in remove_indices.into_iter().enumerate() {
+        let position = index.checked_sub(shift).unwrap();
+        withdrawals.remove(position);
+    }
+    WITHDRAWALS_LIST.save(deps.storage, sender, &withdrawals)?;
 
-        // Calculate user's total delegated amount by subtracting undelegated amount
-        let mut user_total_delegated = USER_TOTAL_DELEGATED.may_load(
-            deps.storage,
-            key_address.to_string()
-        )?.unwrap_or_default();
+    let send_msg = BankMsg::Send {
+        to_address: recipient,
+        amount: coins(claim_amount, ORAI),
+    };
 
-        user_total_delegated = user_total_delegated
-            .checked_sub(Uint128::from(first_unbond.amount))
-            .unwrap();
+    let msg = CosmosMsg::Bank(send_msg);
+    let answer = to_json_binary(
+        &(ExecuteResponse::Claim {
+            amount: claim_amount.into(),
+            status: ResponseStatus::Success,
+        })
+    )?;
 
-        USER_TOTAL_DELEGATED.save(deps.storage, key_address.to_string(), &user_total_delegated)?;
-        //////////////////////////////////////////
+    Ok(Response::new().add_message(msg).set_data(answer))
+}
 
-        for i in 0..withdrawals.len() {
-            if
-                withdrawals[i].claim_time == MAX_UNIX_TIMESTAMP &&
-                withdrawals[i].amount == first_unbond.amount &&
-                withdrawals[i].timestamp == first_unbond.timestamp
-            {
-                withdrawals[i].claim_time = current_time.checked_add(UNBOUND_TIME).unwrap();
-            }
+/// Time when the withdrawals of the epoch can be claimed, `MAX_UNIX_TIMESTAMP` while it is open.
+pub fn epoch_claim_time(storage: &dyn Storage, epoch_id: u64) -> StdResult<u64> {
+    let claim_time = UNBOND_EPOCHS.may_load(storage, epoch_id)?
+        .and_then(|epoch| epoch.claim_time)
+        .unwrap_or(MAX_UNIX_TIMESTAMP);
+    Ok(claim_time)
+}
+
+/// Withdraws the staking rewards of every validator into the contract. The protocol fee
+/// goes to the treasury and the rest raises the reward index of the share holders.
+pub fn try_harvest_rewards(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config: Config = CONFIG_ITEM.load(deps.storage)?;
+
+    let (messages, total_withdraw_amount, fee) = harvest(&mut deps, &env, &config)?;
+    if total_withdraw_amount == 0 {
+        return Err(
+            ContractError::Std(
+                StdError::generic_err("There is nothing to withdraw from validators")
+            )
+        );
+    }
+
+    let answer = to_json_binary(
+        &(ExecuteResponse::HarvestRewards {
+            amount: Uint128::new(total_withdraw_amount),
+            fee: Uint128::new(fee),
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    Ok(Response::new().add_submessages(messages).set_data(answer))
+}
+
+/// Accounts and withdraws the rewards of every delegation. Runs before any staking message,
+/// those withdraw the rewards of their validators on their own and would strand them.
+/// Returns the messages, the harvested amount and the treasury fee.
+fn harvest(
+    deps: &mut DepsMut,
+    env: &Env,
+    config: &Config
+) -> StdResult<(Vec<SubMsg>, u128, u128)> {
+    let mut msgs: Vec<SubMsg> = Vec::new();
+    let mut total_withdraw_amount: u128 = 0;
+
+    // Removed validators can still hold stake, go through every delegation
+    let delegations = deps.querier.query_all_delegations(env.contract.address.clone())?;
+    for delegation in delegations {
+        let validator = delegation.validator;
+        let delegation = utils::query_delegation(deps, env, &validator)?;
+
+        let can_withdraw = delegation
+            .map(|d| {
+                d.accumulated_rewards
+                    .iter()
+                    .filter(|c| c.denom == ORAI)
+                    .map(|c| c.amount.u128())
+                    .sum::<u128>()
+            })
+            .unwrap_or(0);
+        if can_withdraw == 0 {
+            continue;
         }
-        WITHDRAWALS_LIST.save(deps.storage, key_address.clone(), &withdrawals)?;
 
-        // Pop
-        UNBOND_LIST.pop_front(deps.storage)?;
+        let withdraw_msg = DistributionMsg::WithdrawDelegatorReward { validator };
+        msgs.push(SubMsg::new(CosmosMsg::Distribution(withdraw_msg)));
+
+        total_withdraw_amount += can_withdraw;
     }
 
-    let validators = config.validators;
-    let confirmed_amount = total_batch_undelegate_amount.checked_sub(4).unwrap();
+    if total_withdraw_amount == 0 {
+        return Ok((msgs, 0, 0));
+    }
 
-    let mut messages: Vec<SubMsg> = Vec::with_capacity(2);
+    let set_withdraw_addr_msg = DistributionMsg::SetWithdrawAddress {
+        address: env.contract.address.to_string(),
+    };
+    msgs.insert(0, SubMsg::new(CosmosMsg::Distribution(set_withdraw_addr_msg)));
 
-    for validator in validators {
-        let weight_as_uint128 = Uint128::from(validator.weight);
+    // Without share holders the whole harvest belongs to the treasury
+    let mut delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    let fee = if delegation_state.total_shares == 0 {
+        total_withdraw_amount
+    } else {
+        Uint128::from(total_withdraw_amount).multiply_ratio(config.reward_fee, 100_u128).u128()
+    };
 
-        // Perform the multiplication - Uint128 * Uint128
-        let multiplied = Uint128::from(confirmed_amount).multiply_ratio(
-            weight_as_uint128,
-            Uint128::from(100_u128)
+    delegation_state.distribute_rewards(total_withdraw_amount.checked_sub(fee).unwrap());
+    DELEGATION_STATE.save(deps.storage, &delegation_state)?;
+
+    if fee != 0 {
+        let send_msg = BankMsg::Send {
+            to_address: config.treasury.clone(),
+            amount: coins(fee, ORAI),
+        };
+        msgs.push(SubMsg::new(CosmosMsg::Bank(send_msg)));
+    }
+
+    Ok((msgs, total_withdraw_amount, fee))
+}
+
+pub fn try_claim_rewards(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+    owner: Option<String>
+) -> Result<Response, ContractError> {
+    let owner = owner.unwrap_or(info.sender.to_string());
+    let mut user_info = USER_INFOS.may_load(deps.storage, owner.clone())?.unwrap_or_default();
+
+    // Rewards of a deposit go to whoever withdraws it
+    let recipient = claim_recipient(
+        deps.storage,
+        &env,
+        &user_info.withdrawer_or(&owner),
+        info.sender.as_str(),
+        recipient,
+        Permission::Claim
+    )?;
+
+    // Claim what the delegations earned until now, not only the last harvest
+    let config: Config = CONFIG_ITEM.load(deps.storage)?;
+    let (mut messages, _, _) = harvest(&mut deps, &env, &config)?;
+    let delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+
+    if user_info.auto_compound {
+        return Err(
+            ContractError::Std(StdError::generic_err("Rewards of this account are compounded"))
         );
+    }
 
-        // Now, `multiplied` is Uint128, but we want the result as u128
-        let individual_amount: u128 = multiplied.u128();
+    let amount = user_info.pending_rewards;
+    if amount == 0 {
+        return Err(ContractError::Std(StdError::generic_err("Nothing to claim")));
+    }
 
-        let undelegate_msg = StakingMsg::Undelegate {
-            validator: validator.address,
-            amount: coin(individual_amount, ORAI),
-        };
-        let msg = CosmosMsg::Staking(undelegate_msg);
-        messages.push(SubMsg::new(msg));
+    user_info.pending_rewards = 0;
+    USER_INFOS.save(deps.storage, owner, &user_info)?;
+
+    let send_msg = BankMsg::Send {
+        to_address: recipient,
+        amount: coins(amount, ORAI),
+    };
+    messages.push(SubMsg::new(CosmosMsg::Bank(send_msg)));
+
+    let answer = to_json_binary(
+        &(ExecuteResponse::ClaimRewards {
+            amount: Uint128::new(amount),
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    Ok(Response::new().add_submessages(messages).set_data(answer))
+}
+
+/// Compounding spends the rewards, so only whoever can claim them may switch it.
+pub fn try_set_compounding(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    enabled: bool,
+    owner: Option<String>
+) -> Result<Response, ContractError> {
+    let owner = owner.unwrap_or(info.sender.to_string());
+    let mut user_info = match USER_INFOS.may_load(deps.storage, owner.clone())? {
+        Some(user_info) => user_info,
+        None => {
+            return Err(ContractError::Std(StdError::generic_err("User has no deposit")));
+        }
+    };
+
+    assert_permission(
+        deps.storage,
+        &env,
+        &user_info.withdrawer_or(&owner),
+        info.sender.as_str(),
+        Permission::Claim
+    )?;
+
+    user_info.auto_compound = enabled;
+    USER_INFOS.save(deps.storage, owner, &user_info)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "set compounding")
+            .add_attribute("enabled", enabled.to_string())
+    )
+}
+
+/// Delegates the pending rewards of a compounding user and credits them to the ORAI
+/// deposit. Returns the delegation messages.
+fn compound_rewards(
+    storage: &mut dyn Storage,
+    config: &Config,
+    address: &str,
+    user_info: &mut state::UserInfo
+) -> StdResult<Vec<SubMsg>> {
+    let mut delegation_state = DELEGATION_STATE.may_load(storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+
+    let amount = user_info.pending_rewards;
+    if !user_info.auto_compound || amount == 0 {
+        return Ok(vec![]);
     }
 
+    let shares = delegation_state.mint(amount);
+    DELEGATION_STATE.save(storage, &delegation_state)?;
+
+    user_info.pending_rewards = 0;
+    user_info.shares = user_info.shares.checked_add(shares).unwrap();
+    user_info.orai_deposit = user_info.orai_deposit.checked_add(amount).unwrap();
+
+    let withdrawer = user_info.withdrawer_or(address);
+    let user_total_delegated = USER_TOTAL_DELEGATED.may_load(
+        storage,
+        withdrawer.clone()
+    )?.unwrap_or_default();
+    USER_TOTAL_DELEGATED.save(
+        storage,
+        withdrawer,
+        &user_total_delegated.checked_add(Uint128::from(amount)).unwrap()
+    )?;
+
+    Ok(delegate_by_weights(&config.validators, amount))
+}
+
+/// Compounds the pending rewards and re-evaluates the tier of `address` at the current
+/// price according to the tier policy. Anyone can refresh any address.
+pub fn try_refresh_tier(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: Option<String>
+) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let sender = address.unwrap_or(info.sender.to_string());
+    let mut user_info = match USER_INFOS.may_load(deps.storage, sender.clone())? {
+        Some(user_info) => user_info,
+        None => {
+            return Err(ContractError::Std(StdError::generic_err("User has no deposit")));
+        }
+    };
+
+    sync_slashing(&mut deps, &env, &config)?;
+    let (mut messages, _, _) = harvest(&mut deps, &env, &config)?;
+    messages.extend(compound_rewards(deps.storage, &config, &sender, &mut user_info)?);
+
+    let oracle = OraiPriceOracle::new(deps.as_ref(), &env)?;
+    oracle.check(deps.storage, &config, env.block.time.seconds())?;
+
+    let usd_deposit = oracle
+        .usd_amount(user_info.orai_deposit)
+        .checked_add(user_info.stable_usd_deposit(&config))
+        .unwrap();
+    user_info.end_lock(&config, env.block.time.seconds());
+    user_info.reprice(&config, usd_deposit, env.block.time.seconds());
+
+    messages.extend(save_user_info(deps.storage, &config, &sender, &user_info, &env)?);
+
     let answer = to_json_binary(
-        &(ExecuteResponse::Withdraw {
+        &(ExecuteResponse::RefreshTier {
+            usd_deposit: Uint128::new(user_info.usd_deposit),
+            orai_deposit: Uint128::new(user_info.orai_deposit),
+            tier: user_info.tier,
             status: ResponseStatus::Success,
         })
     )?;
@@ -675,135 +2143,256 @@ pub fn try_batch_unbond(deps: DepsMut, env: Env) -> Result<Response, ContractErr
     Ok(Response::new().add_submessages(messages).set_data(answer))
 }
 
-pub fn try_claim(
+/// Confirms the deposit is still in use, so the tier does not expire.
+pub fn try_renew(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: Option<String>
+) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let sender = owner.unwrap_or(info.sender.to_string());
+    assert_permission(deps.storage, &env, &sender, info.sender.as_str(), Permission::Refresh)?;
+    let mut user_info = match USER_INFOS.may_load(deps.storage, sender.clone())? {
+        Some(user_info) => user_info,
+        None => {
+            return Err(ContractError::Std(StdError::generic_err("User has no deposit")));
+        }
+    };
+
+    user_info.renewed_at = env.block.time.seconds();
+    let messages = save_user_info(deps.storage, &config, &sender, &user_info, &env)?;
+
+    let mut response = Response::new()
+        .add_submessages(messages)
+        .add_attribute("action", "renewed tier");
+    if let Some(tier_validity) = config.tier_validity {
+        let expires_at = user_info.renewed_at.saturating_add(tier_validity);
+        response = response.add_attribute("expires_at", expires_at.to_string());
+    }
+
+    Ok(response)
+}
+
+/// Lets `operator` act for the sender with the given permissions until `expires`.
+pub fn try_approve(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    recipient: Option<String>,
-    start: Option<u32>,
-    limit: Option<u32>
+    operator: String,
+    permissions: Vec<Permission>,
+    expires: Option<u64>
 ) -> Result<Response, ContractError> {
-    let config = CONFIG_ITEM.load(deps.storage)?;
-    config.assert_contract_active()?;
+    let operator = deps.api.addr_validate(&operator)?.to_string();
+    if operator == info.sender {
+        return Err(ContractError::Std(StdError::generic_err("Cannot approve yourself")));
+    }
 
-    let sender = info.sender.to_string();
-    let mut withdrawals: Vec<UserWithdrawal> = WITHDRAWALS_LIST.may_load(
-        deps.storage,
-        sender
-    )?.unwrap_or_default();
+    if permissions.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err("Permissions list is empty")));
+    }
 
-    let length = withdrawals.len();
+    if let Some(expires) = expires {
+        if expires <= env.block.time.seconds() {
+            return Err(ContractError::Std(StdError::generic_err("Approval is already expired")));
+        }
+    }
 
-    if length == 0 {
-        return Err(ContractError::Std(StdError::generic_err("Nothing to claim")));
+    let approval = Approval {
+        permissions,
+        expires,
+    };
+    APPROVALS.save(deps.storage, (info.sender.to_string(), operator.clone()), &approval)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "approve")
+            .add_attribute("operator", operator)
+    )
+}
+
+pub fn try_revoke(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    operator: String
+) -> Result<Response, ContractError> {
+    let key = (info.sender.to_string(), operator.clone());
+    if !APPROVALS.has(deps.storage, key.clone()) {
+        return Err(ContractError::Std(StdError::generic_err("Approval not found")));
     }
 
-    let recipient = recipient.unwrap_or(info.sender.to_string());
-    let start: usize = start.unwrap_or(0) as usize;
-    let limit = limit.unwrap_or(50) as usize;
-    let withdrawals_iter: std::iter::Take<
-        std::iter::Skip<std::slice::Iter<'_, UserWithdrawal>>
-    > = withdrawals.iter().skip(start).take(limit);
+    APPROVALS.remove(deps.storage, key);
 
-    let current_time = env.block.time.seconds();
-    let mut remove_indices = Vec::new();
-    let mut claim_amount = 0u128;
+    Ok(
+        Response::new()
+            .add_attribute("action", "revoke")
+            .add_attribute("operator", operator)
+    )
+}
 
-    for (index, withdrawal) in withdrawals_iter.enumerate() {
-        let claim_time = withdrawal.claim_time;
+/// Proposes `address` as the wallet that shows the tier of the sender. Replaces an earlier
+/// proposal, an accepted link has to be removed with `unlink` first.
+pub fn try_propose_link(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String
+) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
 
-        if current_time >= claim_time {
-            remove_indices.push(index);
-            claim_amount = claim_amount.checked_add(withdrawal.amount).unwrap();
-        }
+    let sender = info.sender.to_string();
+    let address = deps.api.addr_validate(&address)?.to_string();
+    if address == sender {
+        return Err(ContractError::Std(StdError::generic_err("Cannot link yourself")));
     }
 
-    if claim_amount == 0 {
-        return Err(ContractError::Std(StdError::generic_err("Nothing to claim")));
+    if LINKS.has(deps.storage, sender.clone()) {
+        return Err(ContractError::Std(StdError::generic_err("Another address is already linked")));
     }
 
-    for (shift, index) in remove_indices.into_iter().enumerate() {
-        let position = index.checked_sub(shift).unwrap();
-        withdrawals.remove(position);
+    if LINKED_OWNERS.has(deps.storage, sender.clone()) {
+        return Err(
+            ContractError::Std(StdError::generic_err("Linked addresses cannot link further"))
+        );
     }
 
-    let send_msg = BankMsg::Send {
-        to_address: recipient,
-        amount: coins(claim_amount, ORAI),
-    };
-
-    let msg = CosmosMsg::Bank(send_msg);
-    let answer = to_json_binary(
-        &(ExecuteResponse::Claim {
-            amount: claim_amount.into(),
-            status: ResponseStatus::Success,
-        })
-    )?;
+    LINK_PROPOSALS.save(deps.storage, sender, &address)?;
 
-    Ok(Response::new().add_message(msg).set_data(answer))
+    Ok(
+        Response::new()
+            .add_attribute("action", "propose_link")
+            .add_attribute("address", address)
+    )
 }
 
-pub fn try_withdraw_rewards(
+/// Accepts the link proposed by `owner`. The sender must not have a deposit or link of its own.
+pub fn try_accept_link(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    recipient: Option<String>
+    owner: String
 ) -> Result<Response, ContractError> {
-    let config: Config = CONFIG_ITEM.load(deps.storage)?;
-    if info.sender.clone() != config.admin {
-        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let sender = info.sender.to_string();
+    let proposed = LINK_PROPOSALS.may_load(deps.storage, owner.clone())?;
+    if proposed.as_deref() != Some(sender.as_str()) {
+        return Err(ContractError::Std(StdError::generic_err("No link proposed")));
     }
 
-    let admin = config.admin;
-    let recipient = recipient.unwrap_or(admin);
-    let mut msgs: Vec<CosmosMsg> = Vec::new();
-    let set_withdraw_addr_msg = DistributionMsg::SetWithdrawAddress { address: recipient };
-    msgs.push(CosmosMsg::Distribution(set_withdraw_addr_msg));
+    if LINKED_OWNERS.has(deps.storage, sender.clone()) {
+        return Err(ContractError::Std(StdError::generic_err("Already linked")));
+    }
 
-    let mut total_withdraw_amount: u128 = 0;
+    if LINKS.has(deps.storage, sender.clone()) || LINK_PROPOSALS.has(deps.storage, sender.clone()) {
+        return Err(
+            ContractError::Std(StdError::generic_err("Owners cannot be linked to another address"))
+        );
+    }
 
-    let validators = &config.validators;
-    for validator_it in validators {
-        let validator = validator_it.clone().address;
-        let delegation = utils::query_delegation(&deps, &env, &validator);
+    if USER_INFOS.has(deps.storage, sender.clone()) {
+        return Err(ContractError::Std(StdError::generic_err("Withdraw your deposit first")));
+    }
 
-        let can_withdraw = delegation
-            .map(|d| d.unwrap().accumulated_rewards[0].amount.u128())
-            .unwrap_or(0);
+    let link = WalletLink {
+        owner: owner.clone(),
+        linked_at: env.block.time.seconds(),
+    };
+    LINK_PROPOSALS.remove(deps.storage, owner.clone());
+    LINKS.save(deps.storage, owner.clone(), &sender)?;
+    LINKED_OWNERS.save(deps.storage, sender.clone(), &link)?;
+    let messages = move_tier(deps.storage, &config, &owner, &sender, true, &env)?;
+
+    Ok(
+        Response::new()
+            .add_submessages(messages)
+            .add_attribute("action", "accept_link")
+            .add_attribute("owner", owner)
+    )
+}
 
-        let withdraw_msg = DistributionMsg::WithdrawDelegatorReward { validator };
+/// Removes the link or pending proposal of the sender, on either side of it.
+pub fn try_unlink(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let sender = info.sender.to_string();
 
-        msgs.push(CosmosMsg::Distribution(withdraw_msg));
+    let link = LINKED_OWNERS.may_load(deps.storage, sender.clone())?;
+    let (owner, linked) = if let Some(link) = link {
+        (link.owner, sender)
+    } else if let Some(linked) = LINKS.may_load(deps.storage, sender.clone())? {
+        (sender, linked)
+    } else if LINK_PROPOSALS.has(deps.storage, sender.clone()) {
+        LINK_PROPOSALS.remove(deps.storage, sender);
+        return Ok(Response::new().add_attribute("action", "unlink"));
+    } else {
+        return Err(ContractError::Std(StdError::generic_err("Nothing to unlink")));
+    };
 
-        total_withdraw_amount += can_withdraw;
+    LINKS.remove(deps.storage, owner.clone());
+    LINKED_OWNERS.remove(deps.storage, linked.clone());
+    let messages = move_tier(deps.storage, &config, &owner, &linked, false, &env)?;
+
+    Ok(
+        Response::new()
+            .add_submessages(messages)
+            .add_attribute("action", "unlink")
+            .add_attribute("owner", owner)
+            .add_attribute("linked", linked)
+    )
+}
+
+/// Checks that `sender` is `owner` or an operator that `owner` approved for `permission`.
+/// Returns whether an operator is acting.
+fn assert_permission(
+    storage: &dyn Storage,
+    env: &Env,
+    owner: &str,
+    sender: &str,
+    permission: Permission
+) -> Result<bool, ContractError> {
+    if owner == sender {
+        return Ok(false);
     }
 
-    if total_withdraw_amount == 0 {
+    let approval = APPROVALS.may_load(storage, (owner.to_string(), sender.to_string()))?;
+    match approval {
+        Some(approval) if approval.allows(&permission, env.block.time.seconds()) => Ok(true),
+        _ => Err(ContractError::Std(StdError::generic_err("Unauthorized"))),
+    }
+}
+
+/// Resolves who receives a claim of `owner`. Operators cannot send the funds elsewhere.
+fn claim_recipient(
+    storage: &dyn Storage,
+    env: &Env,
+    owner: &str,
+    sender: &str,
+    recipient: Option<String>,
+    permission: Permission
+) -> Result<String, ContractError> {
+    let is_operator = assert_permission(storage, env, owner, sender, permission)?;
+    if is_operator && matches!(recipient.as_deref(), Some(r) if r != owner) {
         return Err(
-            ContractError::Std(
-                StdError::generic_err("There is nothing to withdraw from validators")
-            )
+            ContractError::Std(StdError::generic_err("Operators cannot choose the recipient"))
         );
     }
 
-    let answer = to_json_binary(
-        &(ExecuteResponse::WithdrawRewards {
-            amount: Uint128::new(total_withdraw_amount),
-            status: ResponseStatus::Success,
-        })
-    )?;
-
-    Ok(Response::new().add_messages(msgs).set_data(answer))
+    Ok(recipient.unwrap_or(owner.to_string()))
 }
 
 pub fn try_redelegate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     new_validator_address: String,
     old_validator_address: String,
     delegate_ratio: u128,
-    recipient: Option<String>
+    _recipient: Option<String>
 ) -> Result<Response, ContractError> {
     let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
     if info.sender.clone() != config.admin {
@@ -894,7 +2483,6 @@ pub fn try_redelegate(
     }
 
     let delegation = delegation.unwrap();
-    let can_withdraw = delegation.accumulated_rewards[0].amount.u128();
     let can_redelegate = delegation.can_redelegate.amount.u128();
     let delegated_amount = delegation.amount.amount.u128();
 
@@ -942,46 +2530,488 @@ pub fn try_redelegate(
                 weight: new_validator_weight,
             });
         }
-        if delegate_ratio == 100 {
-            config.validators.remove(old_validator_position);
+        if delegate_ratio == 100 {
+            config.validators.remove(old_validator_position);
+        }
+    }
+    CONFIG_ITEM.save(deps.storage, &config)?;
+
+    // The rewards of the old validator belong to the share holders
+    let (mut messages, _, _) = harvest(&mut deps, &env, &config)?;
+
+    let redelegated_amount = can_redelegate
+        .checked_mul(delegate_ratio)
+        .unwrap()
+        .checked_div(100)
+        .unwrap();
+    let coin = coin(redelegated_amount, ORAI);
+    let redelegate_msg = StakingMsg::Redelegate {
+        src_validator: old_validator_address,
+        dst_validator: new_validator_address,
+        amount: coin,
+    };
+
+    messages.push(SubMsg::new(CosmosMsg::Staking(redelegate_msg)));
+    let answer = to_json_binary(
+        &(ExecuteResponse::Redelegate {
+            amount: Uint128::new(can_redelegate),
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    return Ok(Response::new().add_submessages(messages).set_data(answer));
+}
+
+fn assert_validator_weights(validators: &[ValidatorWithWeight]) -> Result<(), ContractError> {
+    let total_weight: u128 = validators
+        .iter()
+        .map(|v| v.weight)
+        .sum();
+
+    if total_weight != 100 {
+        return Err(
+            ContractError::Std(StdError::generic_err("The sum of the total weight must be 100!"))
+        );
+    }
+
+    let has_duplicates = validators
+        .iter()
+        .enumerate()
+        .any(|(i, v)| validators[..i].iter().any(|other| other.address == v.address));
+    if has_duplicates {
+        return Err(ContractError::Std(StdError::generic_err("Duplicated validator address")));
+    }
+
+    Ok(())
+}
+
+/// Adds a validator with zero weight, `SetValidatorWeights` gives it a share of the deposits.
+pub fn try_add_validator(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
+
+    if config.validators.iter().any(|v| v.address == address) {
+        return Err(ContractError::Std(StdError::generic_err("Validator already exists")));
+    }
+
+    if deps.querier.query_validator(address.clone())?.is_none() {
+        return Err(ContractError::Std(StdError::generic_err("Validator is not active")));
+    }
+
+    config.validators.push(ValidatorWithWeight {
+        address: address.clone(),
+        weight: 0,
+    });
+    config.save(deps.storage)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "added validator")
+            .add_attribute("validator", address)
+    )
+}
+
+/// Removes a validator that has no weight and no delegation left.
+pub fn try_remove_validator(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
+
+    let position = match config.validators.iter().position(|v| v.address == address) {
+        Some(position) => position,
+        None => {
+            return Err(ContractError::Std(StdError::generic_err("Validator does not exist")));
+        }
+    };
+
+    if config.validators[position].weight != 0 {
+        return Err(
+            ContractError::Std(
+                StdError::generic_err("Set the validator weight to zero before removing it")
+            )
+        );
+    }
+
+    let delegation = utils::query_delegation(&deps, &env, &address)?;
+    if delegation.map(|d| !d.amount.amount.is_zero()).unwrap_or(false) {
+        return Err(
+            ContractError::Std(
+                StdError::generic_err("Validator still has delegations, rebalance first")
+            )
+        );
+    }
+
+    config.validators.remove(position);
+    config.save(deps.storage)?;
+
+    Ok(
+        Response::new()
+            .add_attribute("action", "removed validator")
+            .add_attribute("validator", address)
+    )
+}
+
+/// Replaces the weights of the current validators, the weights must sum to 100.
+pub fn try_set_validator_weights(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    validators: Vec<ValidatorWithWeight>
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    if info.sender.clone() != config.admin {
+        return Err(ContractError::Std(StdError::generic_err("Unauthorized")));
+    }
+
+    assert_validator_weights(&validators)?;
+
+    let same_set =
+        validators.len() == config.validators.len() &&
+        validators.iter().all(|v| config.validators.iter().any(|c| c.address == v.address));
+    if !same_set {
+        return Err(
+            ContractError::Std(
+                StdError::generic_err(
+                    "Weights must be set for exactly the current validators, use AddValidator or RemoveValidator to change the set"
+                )
+            )
+        );
+    }
+
+    config.validators = validators;
+    config.save(deps.storage)?;
+
+    Ok(Response::new().add_attribute("action", "changed validator weights"))
+}
+
+/// Moves stake from validators above their target weight to the ones below it.
+pub fn try_rebalance(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config: Config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let (harvest_messages, _, _) = harvest(&mut deps, &env, &config)?;
+
+    let mut delegated: Vec<(u128, u128)> = Vec::with_capacity(config.validators.len());
+    for validator in config.validators.iter() {
+        let delegation = utils::query_delegation(&deps, &env, &validator.address)?;
+        let amounts = delegation
+            .map(|d| (d.amount.amount.u128(), d.can_redelegate.amount.u128()))
+            .unwrap_or((0, 0));
+        delegated.push(amounts);
+    }
+
+    let total_delegated: u128 = delegated
+        .iter()
+        .map(|(amount, _)| amount)
+        .sum();
+
+    let mut surpluses: Vec<(String, u128)> = Vec::new();
+    let mut deficits: Vec<(String, u128)> = Vec::new();
+    for (validator, (amount, can_redelegate)) in config.validators.iter().zip(delegated) {
+        let target = Uint128::from(total_delegated)
+            .multiply_ratio(validator.weight, 100_u128)
+            .u128();
+
+        if amount > target {
+            let surplus = (amount - target).min(can_redelegate);
+            if surplus > 0 {
+                surpluses.push((validator.address.clone(), surplus));
+            }
+        } else if target > amount {
+            deficits.push((validator.address.clone(), target - amount));
+        }
+    }
+
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    let mut total_redelegated: u128 = 0;
+    let mut deficit_iter = deficits.into_iter().peekable();
+    for (src_validator, mut surplus) in surpluses {
+        while surplus > 0 {
+            let (dst_validator, deficit) = match deficit_iter.peek_mut() {
+                Some(deficit) => deficit,
+                None => {
+                    break;
+                }
+            };
+
+            let amount = surplus.min(*deficit);
+            messages.push(
+                CosmosMsg::Staking(StakingMsg::Redelegate {
+                    src_validator: src_validator.clone(),
+                    dst_validator: dst_validator.clone(),
+                    amount: coin(amount, ORAI),
+                })
+            );
+
+            surplus -= amount;
+            *deficit -= amount;
+            total_redelegated += amount;
+            if *deficit == 0 {
+                deficit_iter.next();
+            }
+        }
+    }
+
+    if messages.is_empty() {
+        return Err(
+            ContractError::Std(StdError::generic_err("Delegations already match the weights"))
+        );
+    }
+
+    let answer = to_json_binary(
+        &(ExecuteResponse::Redelegate {
+            amount: Uint128::new(total_redelegated),
+            status: ResponseStatus::Success,
+        })
+    )?;
+
+    Ok(
+        Response::new()
+            .add_submessages(harvest_messages)
+            .add_messages(messages)
+            .set_data(answer)
+            .add_attribute("action", "rebalanced validators")
+    )
+}
+
+/// Moves the stake away from validators that left the active set and gives their
+/// weight to the healthy validators in proportion to the current weights.
+pub fn try_check_validators(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG_ITEM.load(deps.storage)?;
+    config.assert_contract_active()?;
+
+    let (harvest_messages, _, _) = harvest(&mut deps, &env, &config)?;
+
+    let mut healthy: Vec<bool> = Vec::with_capacity(config.validators.len());
+    for validator in config.validators.iter() {
+        let active = deps.querier.query_validator(validator.address.clone())?.is_some();
+        healthy.push(active);
+    }
+
+    if !healthy.iter().any(|h| *h) {
+        return Err(
+            ContractError::Std(StdError::generic_err("No healthy validators to move the stake to"))
+        );
+    }
+
+    let mut evacuated: Vec<(String, u128)> = Vec::new();
+    for (validator, active) in config.validators.iter().zip(healthy.iter()) {
+        if *active {
+            continue;
+        }
+
+        let delegation = utils::query_delegation(&deps, &env, &validator.address)?;
+        let can_redelegate = delegation.map(|d| d.can_redelegate.amount.u128()).unwrap_or(0);
+        if validator.weight == 0 && can_redelegate == 0 {
+            continue;
         }
+
+        evacuated.push((validator.address.clone(), can_redelegate));
     }
-    CONFIG_ITEM.save(deps.storage, &config)?;
 
-    let mut messages = Vec::with_capacity(2);
-    if can_withdraw != 0 {
-        let admin = config.admin;
-        let _recipient = recipient.unwrap_or(admin);
-        let withdraw_msg: DistributionMsg = DistributionMsg::WithdrawDelegatorReward {
-            validator: old_validator_address.clone(),
+    if evacuated.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err("All validators are healthy")));
+    }
+
+    // Rewrite the weights, the rounding remainder goes to the heaviest healthy validator
+    let healthy_weight: u128 = config.validators
+        .iter()
+        .zip(healthy.iter())
+        .filter(|(_, active)| **active)
+        .map(|(v, _)| v.weight)
+        .sum();
+    let healthy_count = healthy
+        .iter()
+        .filter(|h| **h)
+        .count() as u128;
+
+    for (validator, active) in config.validators.iter_mut().zip(healthy.iter()) {
+        validator.weight = match (*active, healthy_weight) {
+            (false, _) => 0,
+            (true, 0) => 100 / healthy_count,
+            (true, _) => (validator.weight * 100) / healthy_weight,
         };
+    }
+
+    let total_weight: u128 = config.validators
+        .iter()
+        .map(|v| v.weight)
+        .sum();
+    let heaviest = config.validators
+        .iter_mut()
+        .zip(healthy.iter())
+        .filter(|(_, active)| **active)
+        .map(|(v, _)| v)
+        .max_by_key(|v| v.weight)
+        .unwrap();
+    heaviest.weight += 100 - total_weight;
+    let heaviest_address = heaviest.address.clone();
+
+    config.save(deps.storage)?;
+
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    let mut event = Event::new("tier_validators_evacuated");
+    for (src_validator, amount) in evacuated {
+        event = event.add_attribute("validator", src_validator.clone());
+        if amount == 0 {
+            continue;
+        }
+
+        let mut remaining = amount;
+        for validator in config.validators.iter().filter(|v| v.weight > 0) {
+            let individual_amount = Uint128::from(amount)
+                .multiply_ratio(validator.weight, 100_u128)
+                .u128()
+                .min(remaining);
+            if individual_amount == 0 {
+                continue;
+            }
 
-        let msg = CosmosMsg::Distribution(withdraw_msg);
+            messages.push(
+                CosmosMsg::Staking(StakingMsg::Redelegate {
+                    src_validator: src_validator.clone(),
+                    dst_validator: validator.address.clone(),
+                    amount: coin(individual_amount, ORAI),
+                })
+            );
+            remaining -= individual_amount;
+        }
 
-        messages.push(msg);
+        if remaining > 0 {
+            messages.push(
+                CosmosMsg::Staking(StakingMsg::Redelegate {
+                    src_validator: src_validator.clone(),
+                    dst_validator: heaviest_address.clone(),
+                    amount: coin(remaining, ORAI),
+                })
+            );
+        }
     }
 
-    let redelegated_amount = can_redelegate
-        .checked_mul(delegate_ratio)
-        .unwrap()
-        .checked_div(100)
-        .unwrap();
-    let coin = coin(redelegated_amount, ORAI);
-    let redelegate_msg = StakingMsg::Redelegate {
-        src_validator: old_validator_address,
-        dst_validator: new_validator_address,
-        amount: coin,
+    Ok(
+        Response::new()
+            .add_submessages(harvest_messages)
+            .add_messages(messages)
+            .add_event(event)
+            .add_attribute("action", "checked validators")
+    )
+}
+
+pub fn query_validators_health(deps: Deps, env: Env) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+
+    let mut validators: Vec<ValidatorHealth> = Vec::with_capacity(config.validators.len());
+    for validator in config.validators.iter() {
+        let active = deps.querier.query_validator(validator.address.clone())?.is_some();
+        let delegated = deps.querier
+            .query_delegation(env.contract.address.clone(), validator.address.clone())
+            .unwrap_or(None)
+            .map(|d| d.amount.amount)
+            .unwrap_or_default();
+
+        validators.push(ValidatorHealth {
+            address: validator.address.clone(),
+            weight: validator.weight,
+            active,
+            delegated,
+        });
+    }
+
+    Ok(QueryResponse::ValidatorsHealth { validators })
+}
+
+/// Snapshot of the funds the contract manages and owes.
+pub fn query_state(deps: Deps, env: Env) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let contract_address = env.contract.address.clone();
+
+    let delegations = deps.querier.query_all_delegations(contract_address.clone())?;
+    let total_delegated: u128 = delegations
+        .iter()
+        .map(|d| d.amount.amount.u128())
+        .sum();
+    let delegated_to = |address: &str| -> u128 {
+        delegations
+            .iter()
+            .find(|d| d.validator == address)
+            .map(|d| d.amount.amount.u128())
+            .unwrap_or_default()
     };
 
-    messages.push(CosmosMsg::Staking(redelegate_msg));
-    let answer = to_json_binary(
-        &(ExecuteResponse::Redelegate {
-            amount: Uint128::new(can_redelegate),
-            status: ResponseStatus::Success,
-        })
-    )?;
+    let mut validators: Vec<ValidatorState> = Vec::with_capacity(delegations.len());
+    for validator in config.validators.iter() {
+        validators.push(ValidatorState {
+            address: validator.address.clone(),
+            weight: validator.weight,
+            target: Uint128::new((total_delegated * validator.weight) / 100),
+            delegated: Uint128::new(delegated_to(&validator.address)),
+        });
+    }
+
+    // Delegations left on validators that were removed from the set
+    for delegation in delegations.iter() {
+        if !config.validators.iter().any(|v| v.address == delegation.validator) {
+            validators.push(ValidatorState {
+                address: delegation.validator.clone(),
+                weight: 0,
+                target: Uint128::zero(),
+                delegated: delegation.amount.amount,
+            });
+        }
+    }
+
+    let current_time = env.block.time.seconds();
+    let mut queued = 0u128;
+    let mut unbonding = 0u128;
+    let mut claimable = 0u128;
+    let epochs_iter = UNBOND_EPOCHS.range(
+        deps.storage,
+        None,
+        None,
+        cosmwasm_std::Order::Ascending
+    );
+    for it in epochs_iter {
+        let (_, epoch) = it?;
+        match epoch.claim_time {
+            None => {
+                queued = queued.checked_add(epoch.amount).unwrap();
+            }
+            Some(claim_time) if claim_time > current_time => {
+                unbonding = unbonding.checked_add(epoch.unclaimed()).unwrap();
+            }
+            Some(_) => {
+                claimable = claimable.checked_add(epoch.unclaimed()).unwrap();
+            }
+        }
+    }
+
+    let exchange_rate = OraiPriceOracle::new(deps, &env)
+        .ok()
+        .map(|oracle| Uint128::new(oracle.exchange_rate()));
+    let balance = deps.querier.query_all_balances(contract_address)?;
 
-    return Ok(Response::new().add_messages(messages).set_data(answer));
+    Ok(QueryResponse::State {
+        validators,
+        total_delegated: Uint128::new(total_delegated),
+        queued: Uint128::new(queued),
+        unbonding: Uint128::new(unbonding),
+        claimable: Uint128::new(claimable),
+        exchange_rate,
+        balance,
+    })
 }
 
 fn query_config(deps: Deps) -> StdResult<QueryResponse> {
@@ -989,18 +3019,91 @@ fn query_config(deps: Deps) -> StdResult<QueryResponse> {
     config.to_answer()
 }
 
-pub fn query_user_info(deps: Deps, address: String) -> StdResult<QueryResponse> {
+pub fn query_user_info(deps: Deps, env: Env, address: String) -> StdResult<QueryResponse> {
     let config = CONFIG_ITEM.load(deps.storage)?;
     let min_tier = config.min_tier();
+    // Linked addresses report the position of the owner they are linked to, the owner
+    // keeps the minimal tier until the link is removed
+    let lends_tier = LINKS.has(deps.storage, address.clone());
+    let address = match LINKED_OWNERS.may_load(deps.storage, address.clone())? {
+        Some(link) => link.owner,
+        None => address,
+    };
     let user_info = USER_INFOS.may_load(deps.storage, address)?.unwrap_or(state::UserInfo {
         tier: min_tier,
         ..Default::default()
     });
 
-    let answer = user_info.to_answer();
+    let mut answer = user_info.to_answer(&config, env.block.time.seconds());
+    if let QueryResponse::UserInfo { tier, .. } = &mut answer {
+        if lends_tier {
+            *tier = min_tier;
+        }
+    }
     return Ok(answer);
 }
 
+/// Position, withdrawals and rewards of `address` in one answer. Withdrawals are listed
+/// for the address that receives them, the funder of a deposit if it kept that right.
+pub fn query_account(deps: Deps, env: Env, address: String) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let mut user_info = USER_INFOS.may_load(deps.storage, address.clone())?.unwrap_or(
+        state::UserInfo {
+            tier: config.min_tier(),
+            ..Default::default()
+        }
+    );
+    let delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+
+    let lifetime_deposits = LIFETIME_DEPOSITS.may_load(
+        deps.storage,
+        address.clone()
+    )?.unwrap_or_default();
+
+    let withdrawals = WITHDRAWALS_LIST.may_load(
+        deps.storage,
+        address.clone()
+    )?.unwrap_or_default();
+    let mut queued = 0u128;
+    let mut claimable = 0u128;
+    let mut unbonding: Vec<SerializedWithdrawals> = Vec::new();
+    for withdrawal in withdrawals.iter() {
+        let claim_time = epoch_claim_time(deps.storage, withdrawal.epoch_id)?;
+        if claim_time == MAX_UNIX_TIMESTAMP {
+            queued = queued.checked_add(withdrawal.amount).unwrap();
+        } else if claim_time > now {
+            unbonding.push(withdrawal.to_serialized(claim_time));
+        } else {
+            claimable = claimable.checked_add(withdrawal.amount).unwrap();
+        }
+    }
+
+    // The tier is lent to the linked address while a link exists
+    let tier = if LINKS.has(deps.storage, address.clone()) {
+        config.min_tier()
+    } else {
+        user_info.effective_tier(&config, now)
+    };
+
+    Ok(QueryResponse::Account {
+        tier,
+        timestamp: user_info.timestamp,
+        usd_deposit: Uint128::new(user_info.usd_deposit),
+        orai_deposit: Uint128::new(user_info.orai_deposit),
+        stable_deposits: user_info.stable_deposits,
+        expires_at: config.tier_validity.map(|v| user_info.renewed_at.saturating_add(v)),
+        lifetime_deposits: lifetime_deposits.deposits,
+        lifetime_usd_deposit: Uint128::new(lifetime_deposits.usd_deposit),
+        queued: Uint128::new(queued),
+        unbonding,
+        claimable: Uint128::new(claimable),
+        pending_rewards: Uint128::new(user_info.pending_rewards),
+    })
+}
+
 pub fn query_user_total_delegated(deps: Deps, address: String) -> StdResult<QueryResponse> {
     let user_total_delegated = USER_TOTAL_DELEGATED.may_load(
         deps.storage,
@@ -1029,7 +3132,9 @@ pub fn query_withdrawals(
     for i in start..start + limit {
         let index: usize = i.try_into().unwrap();
         if index < amount {
-            serialized_withdrawals.push(withdrawals[index].to_serialized());
+            let withdrawal = &withdrawals[index];
+            let claim_time = epoch_claim_time(deps.storage, withdrawal.epoch_id)?;
+            serialized_withdrawals.push(withdrawal.to_serialized(claim_time));
         }
     }
 
@@ -1042,21 +3147,432 @@ pub fn query_withdrawals(
 }
 
 pub fn query_unbonds(deps: Deps) -> StdResult<QueryResponse> {
-    let unbond_list = UNBOND_LIST;
-    let amount = unbond_list.len(deps.storage)?;
+    let epoch_id = UNBOND_EPOCH.may_load(deps.storage)?.unwrap_or_default();
 
     let mut serialized_unbonds: Vec<SerializedUnbonds> = Vec::new();
 
-    let unbond_list_iter = unbond_list.iter(deps.storage)?;
+    let unbond_list_iter = EPOCH_UNBONDS.prefix(epoch_id).range(
+        deps.storage,
+        None,
+        None,
+        cosmwasm_std::Order::Ascending
+    );
 
     for it in unbond_list_iter {
-        let user_unbond = it.unwrap();
+        let (_, user_unbond) = it?;
         serialized_unbonds.push(user_unbond.to_serialized());
     }
 
     let answer = QueryResponse::Unbonds {
-        amount: amount.try_into().unwrap(),
+        amount: serialized_unbonds.len().try_into().unwrap(),
         unbonds: serialized_unbonds,
     };
     Ok(answer)
 }
+
+pub fn query_current_epoch(deps: Deps) -> StdResult<QueryResponse> {
+    let epoch_id = UNBOND_EPOCH.may_load(deps.storage)?.unwrap_or_default();
+    let epoch = UNBOND_EPOCHS.may_load(deps.storage, epoch_id)?.unwrap_or_default();
+
+    // An empty epoch starts with the next withdrawal
+    let batch_time = if epoch.amount == 0 {
+        None
+    } else {
+        Some(epoch.start_time.checked_add(BATCH_PERIOD).unwrap())
+    };
+
+    let pending_batch = PENDING_BATCH.may_load(deps.storage)?.map(|b| b.epoch_id);
+
+    let answer = QueryResponse::CurrentEpoch {
+        epoch: epoch.to_serialized(epoch_id),
+        batch_time,
+        pending_batch,
+    };
+    Ok(answer)
+}
+
+pub fn query_epochs(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>
+) -> StdResult<QueryResponse> {
+    let limit = limit.unwrap_or(30).min(100) as usize;
+    let min = start_after.map(Bound::exclusive);
+
+    let epochs_iter = UNBOND_EPOCHS.range(
+        deps.storage,
+        min,
+        None,
+        cosmwasm_std::Order::Ascending
+    ).take(limit);
+
+    let mut epochs: Vec<SerializedEpoch> = Vec::new();
+    for it in epochs_iter {
+        let (epoch_id, epoch) = it?;
+        epochs.push(epoch.to_serialized(epoch_id));
+    }
+
+    Ok(QueryResponse::Epochs { epochs })
+}
+
+/// Lists the stored positions by address, optionally only those with `tier`.
+/// Lists the deposits with the tier they give now. The `tier` filter uses the tier stored
+/// with the last update of a deposit, like `TierStats`.
+pub fn query_all_users(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    tier: Option<u8>
+) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let now = env.block.time.seconds();
+    let limit = limit.unwrap_or(30).min(100) as usize;
+    let min = start_after.map(Bound::exclusive);
+
+    let mut users: Vec<SerializedUserInfo> = Vec::new();
+    match tier {
+        Some(tier) => {
+            let addresses_iter = TIER_HOLDERS.prefix(tier)
+                .keys(deps.storage, min, None, cosmwasm_std::Order::Ascending)
+                .take(limit);
+
+            for address in addresses_iter {
+                let address = address?;
+                let user_info = USER_INFOS.load(deps.storage, address.clone())?;
+                users.push(user_info.to_serialized(address, &config, now));
+            }
+        }
+        None => {
+            let users_iter = USER_INFOS.range(
+                deps.storage,
+                min,
+                None,
+                cosmwasm_std::Order::Ascending
+            ).take(limit);
+
+            for it in users_iter {
+                let (address, user_info) = it?;
+                users.push(user_info.to_serialized(address, &config, now));
+            }
+        }
+    }
+
+    Ok(QueryResponse::AllUsers { users })
+}
+
+/// Holders and deposits per tier, updated on every write of a deposit. A lock boost that
+/// ended counts until the next write, an expired tier until it is renewed or withdrawn.
+pub fn query_tier_stats(deps: Deps) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+
+    let mut tiers: Vec<SerializedTierStats> = Vec::new();
+    for tier in config.max_tier()..=config.min_tier() {
+        let tier_stats = TIER_STATS.may_load(deps.storage, tier)?.unwrap_or_default();
+        tiers.push(tier_stats.to_serialized(tier));
+    }
+
+    Ok(QueryResponse::TierStats { tiers })
+}
+
+pub fn query_hooks(deps: Deps) -> StdResult<QueryResponse> {
+    let hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(QueryResponse::Hooks { hooks })
+}
+
+pub fn query_link(deps: Deps, address: String) -> StdResult<QueryResponse> {
+    let linked = LINKS.may_load(deps.storage, address.clone())?;
+    let proposed = LINK_PROPOSALS.may_load(deps.storage, address.clone())?;
+    let link = LINKED_OWNERS.may_load(deps.storage, address)?;
+
+    Ok(QueryResponse::Link {
+        linked,
+        proposed,
+        owner: link.as_ref().map(|l| l.owner.clone()),
+        linked_at: link.map(|l| l.linked_at),
+    })
+}
+
+pub fn query_approval(deps: Deps, owner: String, operator: String) -> StdResult<QueryResponse> {
+    let approval = APPROVALS.may_load(deps.storage, (owner, operator.clone()))?;
+
+    Ok(QueryResponse::Approval {
+        approval: approval.map(|a| a.to_serialized(operator)),
+    })
+}
+
+pub fn query_approvals(
+    deps: Deps,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>
+) -> StdResult<QueryResponse> {
+    let limit = limit.unwrap_or(30).min(100) as usize;
+    let min = start_after.map(Bound::exclusive);
+
+    let approvals_iter = APPROVALS.prefix(owner)
+        .range(deps.storage, min, None, cosmwasm_std::Order::Ascending)
+        .take(limit);
+
+    let mut approvals: Vec<SerializedApproval> = Vec::new();
+    for it in approvals_iter {
+        let (operator, approval) = it?;
+        approvals.push(approval.to_serialized(operator));
+    }
+
+    Ok(QueryResponse::Approvals { approvals })
+}
+
+pub fn query_funded_deposits(
+    deps: Deps,
+    funder: String,
+    start_after: Option<String>,
+    limit: Option<u32>
+) -> StdResult<QueryResponse> {
+    let limit = limit.unwrap_or(30).min(100) as usize;
+    let min = start_after.map(Bound::exclusive);
+
+    let deposits_iter = FUNDED_DEPOSITS.prefix(funder)
+        .range(deps.storage, min, None, cosmwasm_std::Order::Ascending)
+        .take(limit);
+
+    let mut deposits: Vec<SerializedFundedDeposit> = Vec::new();
+    for it in deposits_iter {
+        let (beneficiary, funded_deposit) = it?;
+        deposits.push(funded_deposit.to_serialized(beneficiary));
+    }
+
+    Ok(QueryResponse::FundedDeposits { deposits })
+}
+
+pub fn query_user_epochs(deps: Deps, address: String) -> StdResult<QueryResponse> {
+    let withdrawals = WITHDRAWALS_LIST.may_load(deps.storage, address)?.unwrap_or_default();
+
+    let mut epochs: Vec<SerializedUserEpoch> = Vec::new();
+    for withdrawal in withdrawals.iter() {
+        match epochs.iter_mut().find(|e| e.epoch_id == withdrawal.epoch_id) {
+            Some(user_epoch) => {
+                user_epoch.amount += Uint128::from(withdrawal.amount);
+            }
+            None => {
+                let epoch = UNBOND_EPOCHS.may_load(
+                    deps.storage,
+                    withdrawal.epoch_id
+                )?.unwrap_or_default();
+                epochs.push(SerializedUserEpoch {
+                    epoch_id: withdrawal.epoch_id,
+                    amount: Uint128::from(withdrawal.amount),
+                    close_time: epoch.close_time,
+                    claim_time: epoch.claim_time,
+                });
+            }
+        }
+    }
+
+    Ok(QueryResponse::UserEpochs { epochs })
+}
+
+pub fn query_price_observations(deps: Deps) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+
+    let mut observations: Vec<SerializedPriceObservation> = Vec::new();
+    for it in PRICE_OBSERVATIONS.iter(deps.storage)? {
+        observations.push(it?.to_serialized());
+    }
+
+    let answer = QueryResponse::PriceObservations {
+        twap_window: config.twap_window,
+        observations,
+    };
+    Ok(answer)
+}
+
+pub fn query_rewards(deps: Deps, address: String) -> StdResult<QueryResponse> {
+    let mut user_info = USER_INFOS.may_load(deps.storage, address)?.unwrap_or_default();
+
+    let delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+
+    Ok(QueryResponse::Rewards {
+        pending_rewards: Uint128::from(user_info.pending_rewards),
+    })
+}
+
+pub fn query_tier_at(deps: Deps, address: String, timestamp: u64) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    // Links move the tier between the histories of the owner and the linked address
+    let tier = tier_at(deps.storage, &config, &address, timestamp)?;
+
+    Ok(QueryResponse::TierAt { tier })
+}
+
+pub fn query_tier_held(
+    deps: Deps,
+    address: String,
+    start: u64,
+    end: u64
+) -> StdResult<QueryResponse> {
+    if start > end {
+        return Err(StdError::generic_err("Start should not be after the end"));
+    }
+
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let tier = tier_held(deps.storage, &config, &address, start, end)?;
+
+    Ok(QueryResponse::TierHeld { tier })
+}
+
+/// Runs the deposit of `try_deposit` for `address` without changing anything. A deposit
+/// that would fail makes the query fail with the same error.
+pub fn query_simulate_deposit(
+    deps: Deps,
+    env: Env,
+    address: String,
+    amount: Uint128,
+    denom: Option<String>
+) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let now = env.block.time.seconds();
+    let funds = coin(amount.u128(), denom.unwrap_or(ORAI.to_string()));
+    get_received_funds(deps.storage, std::slice::from_ref(&funds))?;
+
+    if let Some(link) = LINKED_OWNERS.may_load(deps.storage, address.clone())? {
+        return Err(StdError::generic_err(format!("{} is linked to {}", address, link.owner)));
+    }
+
+    let user_info = USER_INFOS.may_load(deps.storage, address)?.unwrap_or(state::UserInfo {
+        tier: config.min_tier(),
+        ..Default::default()
+    });
+    let plan = plan_deposit(deps, &env, &config, &funds, user_info, None)?;
+    let user_info = plan.user_info;
+
+    // The missing amounts are in ORAI whatever was deposited
+    let oracle = match plan.oracle {
+        Some(oracle) => oracle,
+        None => OraiPriceOracle::new(deps, &env)?,
+    };
+    let mut next_tiers: Vec<TierRequirement> = Vec::new();
+    for next_tier in (config.max_tier()..user_info.tier).rev() {
+        let next_tier_deposit = user_info.required_usd_deposit(
+            config.deposit_by_tier(next_tier),
+            now
+        );
+        let usd_amount = next_tier_deposit.saturating_sub(user_info.usd_deposit);
+
+        next_tiers.push(TierRequirement {
+            tier: next_tier,
+            usd_amount: Uint128::new(usd_amount),
+            orai_amount: Uint128::new(oracle.orai_amount(usd_amount)),
+        });
+    }
+
+    Ok(QueryResponse::SimulateDeposit {
+        tier: user_info.tier,
+        usd_value: Uint128::new(plan.usd_value),
+        usd_deposit: Uint128::new(user_info.usd_deposit),
+        refund: Uint128::new(plan.refund),
+        next_tiers,
+    })
+}
+
+pub fn query_simulate_withdraw(
+    deps: Deps,
+    env: Env,
+    address: String,
+    amount: Option<Uint128>,
+    target_tier: Option<u8>
+) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let mut user_info = match USER_INFOS.may_load(deps.storage, address)? {
+        Some(user_info) => user_info,
+        None => {
+            return Err(StdError::generic_err("Nothing to withdraw"));
+        }
+    };
+
+    if user_info.lock_end > now {
+        return Err(
+            StdError::generic_err(format!("Deposit is locked until {}", user_info.lock_end))
+        );
+    }
+    user_info.end_lock(&config, now);
+
+    let (shares, stable_deposits, tier) = if amount.is_some() || target_tier.is_some() {
+        let (shares, remaining_info) = partial_withdrawal(
+            &config,
+            user_info,
+            amount,
+            target_tier
+        ).map_err(|err| {
+            match err {
+                ContractError::Std(err) => err,
+                err => StdError::generic_err(err.to_string()),
+            }
+        })?;
+        (shares, vec![], remaining_info.tier)
+    } else {
+        (user_info.shares, user_info.stable_deposits, config.min_tier())
+    };
+
+    let mut delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    let slashed = slashed_amount(deps, &env, &config, &delegation_state)?;
+    delegation_state.total_delegated = delegation_state.total_delegated.saturating_sub(slashed);
+    let amount = delegation_state.amount_for(shares);
+
+    // The withdrawal joins the open epoch, or starts a new one
+    let epoch_id = UNBOND_EPOCH.may_load(deps.storage)?.unwrap_or_default();
+    let epoch_start = UNBOND_EPOCHS.may_load(deps.storage, epoch_id)?
+        .map_or(now, |epoch| epoch.start_time);
+    let claim_time = epoch_start
+        .checked_add(BATCH_PERIOD)
+        .unwrap()
+        .max(now)
+        .checked_add(UNBOUND_TIME)
+        .unwrap();
+
+    Ok(QueryResponse::SimulateWithdraw {
+        amount: Uint128::new(amount),
+        stable_deposits,
+        tier,
+        claim_time,
+    })
+}
+
+/// Shows what `refresh_tier` would do for `address` right now.
+pub fn query_preview_tier(deps: Deps, env: Env, address: String) -> StdResult<QueryResponse> {
+    let config = CONFIG_ITEM.load(deps.storage)?;
+    let mut user_info = USER_INFOS.may_load(deps.storage, address)?.unwrap_or(state::UserInfo {
+        tier: config.min_tier(),
+        ..Default::default()
+    });
+
+    let delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    user_info.settle_rewards(delegation_state.reward_index);
+    if user_info.auto_compound {
+        user_info.orai_deposit = user_info.orai_deposit
+            .checked_add(user_info.pending_rewards)
+            .unwrap();
+    }
+
+    let oracle = OraiPriceOracle::new(deps, &env)?;
+    let usd_deposit = oracle
+        .usd_amount(user_info.orai_deposit)
+        .checked_add(user_info.stable_usd_deposit(&config))
+        .unwrap();
+    user_info.end_lock(&config, env.block.time.seconds());
+    user_info.reprice(&config, usd_deposit, env.block.time.seconds());
+
+    Ok(QueryResponse::PreviewTier {
+        tier: user_info.tier,
+        usd_deposit: Uint128::from(user_info.usd_deposit),
+        downgrade_at: user_info.downgrade_at,
+    })
+}
+
+pub fn query_delegation_state(deps: Deps) -> StdResult<QueryResponse> {
+    let delegation_state = DELEGATION_STATE.may_load(deps.storage)?.unwrap_or_default();
+    Ok(delegation_state.to_answer())
+}
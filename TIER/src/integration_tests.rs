@@ -0,0 +1,1498 @@
+use std::fmt::Debug;
+
+use cosmwasm_std::storage_keys::namespace_with_key;
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{
+    coin,
+    coins,
+    to_json_binary,
+    Addr,
+    Binary,
+    Coin,
+    Decimal,
+    Deps,
+    DepsMut,
+    Empty,
+    Env,
+    Event,
+    MessageInfo,
+    Response,
+    StdError,
+    StdResult,
+    Uint128,
+    Validator,
+};
+use cw_storage_plus::Item;
+use cw_multi_test::error::AnyResult;
+use cw_multi_test::{
+    App,
+    AppBuilder,
+    AppResponse,
+    Contract,
+    ContractWrapper,
+    Executor,
+    StakingInfo,
+    StakingSudo,
+    SudoMsg,
+};
+
+use crate::band::{ BandQueryMsg, ReferenceData };
+use crate::contract::{ execute, instantiate, query, reply, BATCH_PERIOD, ORAI, UNBOUND_TIME };
+use crate::msg::{
+    ContractStatus,
+    ExecuteMsg,
+    InstantiateMsg,
+    LockBoost,
+    OraiswapContract,
+    Permission,
+    PriceOracleSource,
+    QueryMsg,
+    QueryResponse,
+    StableDenom,
+    TierHookMsg,
+    TierPolicy,
+    ValidatorHealth,
+    ValidatorWithWeight,
+};
+
+const ADMIN: &str = "admin";
+const TREASURY: &str = "treasury";
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+const CAROL: &str = "carol";
+const USDC: &str = "usdc";
+const DAI: &str = "dai";
+const DAI_UNIT: u128 = 1_000_000_000_000_000_000;
+const VALIDATORS: [&str; 2] = ["validator1", "validator2"];
+/// Active validator the contract does not delegate to.
+const SPARE_VALIDATOR: &str = "validator3";
+const LOCK_DURATION: u64 = 90 * 24 * 60 * 60;
+const YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// ORAI needed for a deposit worth `usd` at the fixed price of 1 USD.
+const fn orai(usd: u128) -> u128 {
+    usd * 1_000_000 + 1
+}
+
+fn tier_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(execute, instantiate, query).with_reply(reply);
+    Box::new(contract)
+}
+
+/// Band standard reference that answers with the reference data it was last given.
+fn band_contract() -> Box<dyn Contract<Empty>> {
+    const REFERENCE_DATA: Item<ReferenceData> = Item::new("reference_data");
+
+    fn execute(
+        deps: DepsMut,
+        _: Env,
+        _: MessageInfo,
+        msg: ReferenceData
+    ) -> StdResult<Response> {
+        REFERENCE_DATA.save(deps.storage, &msg)?;
+        Ok(Response::new())
+    }
+    fn instantiate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: ReferenceData
+    ) -> StdResult<Response> {
+        execute(deps, env, info, msg)
+    }
+    fn query(deps: Deps, _: Env, _: BandQueryMsg) -> StdResult<Binary> {
+        to_json_binary(&REFERENCE_DATA.load(deps.storage)?)
+    }
+
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
+
+/// Hook that keeps the tier changes it is sent, or fails on every one of them.
+fn hook_contract() -> Box<dyn Contract<Empty>> {
+    const FAILS: Item<bool> = Item::new("fails");
+    const CHANGES: Item<Vec<(String, u8, u8)>> = Item::new("changes");
+
+    fn execute(deps: DepsMut, _: Env, _: MessageInfo, msg: TierHookMsg) -> StdResult<Response> {
+        if FAILS.load(deps.storage)? {
+            return Err(StdError::generic_err("hook is broken"));
+        }
+
+        let TierHookMsg::TierChanged { address, old_tier, new_tier } = msg;
+        let mut changes = CHANGES.may_load(deps.storage)?.unwrap_or_default();
+        changes.push((address, old_tier, new_tier));
+        CHANGES.save(deps.storage, &changes)?;
+        Ok(Response::new())
+    }
+    fn instantiate(deps: DepsMut, _: Env, _: MessageInfo, fails: bool) -> StdResult<Response> {
+        FAILS.save(deps.storage, &fails)?;
+        Ok(Response::new())
+    }
+    fn query(deps: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+        to_json_binary(&CHANGES.may_load(deps.storage)?.unwrap_or_default())
+    }
+
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
+
+fn add_hook(app: &mut App, contract: &Addr, fails: bool) -> Addr {
+    let code_id = app.store_code(hook_contract());
+    let hook = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &fails, &[], "hook", None)
+        .unwrap();
+    let msg = ExecuteMsg::AddHook {
+        contract_addr: hook.to_string(),
+    };
+    run(app, contract, ADMIN, &msg, 0).unwrap();
+    hook
+}
+
+/// Reference data for `usd` micro USD per ORAI, updated at `timestamp`.
+fn reference_data(usd: u128, timestamp: u64) -> ReferenceData {
+    ReferenceData {
+        rate: Uint128::new(usd * 1_000_000_000_000),
+        last_updated_base: timestamp,
+        last_updated_quote: timestamp,
+    }
+}
+
+/// Makes a Band reference with the given data the price oracle of the Tier contract.
+fn use_band(app: &mut App, contract: &Addr, data: ReferenceData) -> Addr {
+    let code_id = app.store_code(band_contract());
+    let band = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &data, &[], "band", None)
+        .unwrap();
+    let msg = ExecuteMsg::ChangePriceOracle {
+        price_oracle: PriceOracleSource::Band {
+            contract: band.to_string(),
+        },
+    };
+    run(app, contract, ADMIN, &msg, 0).unwrap();
+    band
+}
+
+fn set_band_price(app: &mut App, band: &Addr, data: ReferenceData) {
+    app.execute_contract(Addr::unchecked(ADMIN), band.clone(), &data, &[]).unwrap();
+}
+
+fn mock_app() -> App {
+    AppBuilder::new().build(|router, api, storage| {
+        for user in [ALICE, BOB, CAROL] {
+            let funds = vec![
+                coin(10_000_000_000, ORAI),
+                coin(10_000_000_000, USDC),
+                coin(10_000 * DAI_UNIT, DAI)
+            ];
+            router.bank.init_balance(storage, &Addr::unchecked(user), funds).unwrap();
+        }
+
+        let staking_info = StakingInfo {
+            bonded_denom: ORAI.to_string(),
+            unbonding_time: UNBOUND_TIME,
+            apr: Decimal::percent(10),
+        };
+        router.staking.setup(storage, staking_info).unwrap();
+
+        for address in VALIDATORS.into_iter().chain([SPARE_VALIDATOR]) {
+            let validator = Validator {
+                address: address.to_string(),
+                commission: Decimal::zero(),
+                max_commission: Decimal::percent(100),
+                max_change_rate: Decimal::percent(1),
+            };
+            router.staking.add_validator(api, storage, &mock_env().block, validator).unwrap();
+        }
+    })
+}
+
+/// Tiers need 1000, 900 and 100 USD, ORAI costs 1 USD and staking pays 10% a year.
+fn instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        admin: Some(ADMIN.to_string()),
+        validators: VALIDATORS.iter()
+            .map(|address| ValidatorWithWeight {
+                address: address.to_string(),
+                weight: 50,
+            })
+            .collect(),
+        deposits: vec![Uint128::new(1000), Uint128::new(900), Uint128::new(100)],
+        oraiswap_contract: OraiswapContract {
+            orai_swap_router_contract: "router".to_string(),
+            usdt_contract: "usdt".to_string(),
+        },
+        stable_denom: Some(
+            vec![
+                StableDenom {
+                    denom: USDC.to_string(),
+                    decimals: 6,
+                },
+                StableDenom {
+                    denom: DAI.to_string(),
+                    decimals: 18,
+                }
+            ]
+        ),
+        price_oracle: Some(PriceOracleSource::Fixed {
+            exchange_rate: Uint128::new(1_000_000),
+        }),
+        twap_window: None,
+        max_price_deviation: None,
+        max_price_age: None,
+        reward_fee: Some(10),
+        treasury: Some(TREASURY.to_string()),
+        tier_policy: None,
+        lock_boosts: Some(
+            vec![LockBoost {
+                duration: LOCK_DURATION,
+                multiplier: 150,
+            }]
+        ),
+        tier_validity: None,
+    }
+}
+
+fn instantiate_tier(app: &mut App, msg: &InstantiateMsg) -> AnyResult<Addr> {
+    let code_id = app.store_code(tier_contract());
+    app.instantiate_contract(code_id, Addr::unchecked(ADMIN), msg, &[], "tier", None)
+}
+
+/// Instantiates the contract with the defaults of `instantiate_msg` changed by `update`.
+fn setup_with(update: impl FnOnce(&mut InstantiateMsg)) -> (App, Addr) {
+    let mut app = mock_app();
+    let mut msg = instantiate_msg();
+    update(&mut msg);
+    let contract = instantiate_tier(&mut app, &msg).unwrap();
+
+    (app, contract)
+}
+
+fn setup() -> (App, Addr) {
+    setup_with(|_| {})
+}
+
+fn advance(app: &mut App, seconds: u64) {
+    app.update_block(|block| {
+        block.time = block.time.plus_seconds(seconds);
+        block.height += seconds / 5;
+    });
+    // Unbondings are paid out for the block that ends, so end the new one as well
+    app.update_block(|block| {
+        block.height += 1;
+    });
+}
+
+fn run(
+    app: &mut App,
+    contract: &Addr,
+    sender: &str,
+    msg: &ExecuteMsg,
+    funds: u128
+) -> AnyResult<AppResponse> {
+    let funds = if funds == 0 { vec![] } else { coins(funds, ORAI) };
+    app.execute_contract(Addr::unchecked(sender), contract.clone(), msg, &funds)
+}
+
+fn deposit(
+    app: &mut App,
+    contract: &Addr,
+    sender: &str,
+    amount: u128,
+    lock_duration: Option<u64>
+) {
+    run(app, contract, sender, &(ExecuteMsg::Deposit { lock_duration }), amount).unwrap();
+}
+
+fn withdraw(
+    app: &mut App,
+    contract: &Addr,
+    sender: &str,
+    amount: Option<u128>,
+    target_tier: Option<u8>
+) -> AnyResult<AppResponse> {
+    let msg = ExecuteMsg::Withdraw {
+        amount: amount.map(Uint128::new),
+        target_tier,
+        owner: None,
+    };
+    run(app, contract, sender, &msg, 0)
+}
+
+fn batch_unbond(
+    app: &mut App,
+    contract: &Addr,
+    limit: Option<u32>
+) -> AnyResult<AppResponse> {
+    run(app, contract, ADMIN, &(ExecuteMsg::BatchUnbond { limit }), 0)
+}
+
+fn claim(app: &mut App, contract: &Addr, sender: &str) -> AnyResult<AppResponse> {
+    let msg = ExecuteMsg::Claim {
+        recipient: None,
+        start: None,
+        limit: None,
+        owner: None,
+    };
+    run(app, contract, sender, &msg, 0)
+}
+
+fn query_answer(app: &App, contract: &Addr, msg: &QueryMsg) -> QueryResponse {
+    app.wrap().query_wasm_smart(contract, msg).unwrap()
+}
+
+/// Tier, USD and ORAI deposit reported by the `UserInfo` query.
+fn user_info(app: &App, contract: &Addr, address: &str) -> (u8, u128, u128) {
+    let msg = QueryMsg::UserInfo {
+        address: address.to_string(),
+    };
+    match query_answer(app, contract, &msg) {
+        QueryResponse::UserInfo { tier, usd_deposit, orai_deposit, .. } =>
+            (tier, usd_deposit.u128(), orai_deposit.u128()),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+fn tier_at(app: &App, contract: &Addr, address: &str, timestamp: u64) -> u8 {
+    let msg = QueryMsg::TierAt {
+        address: address.to_string(),
+        timestamp,
+    };
+    match query_answer(app, contract, &msg) {
+        QueryResponse::TierAt { tier } => tier,
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+fn tier_held(app: &App, contract: &Addr, address: &str, start: u64, end: u64) -> u8 {
+    let msg = QueryMsg::TierHeld {
+        address: address.to_string(),
+        start,
+        end,
+    };
+    match query_answer(app, contract, &msg) {
+        QueryResponse::TierHeld { tier } => tier,
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+fn pending_rewards(app: &App, contract: &Addr, address: &str) -> u128 {
+    let msg = QueryMsg::Rewards {
+        address: address.to_string(),
+    };
+    match query_answer(app, contract, &msg) {
+        QueryResponse::Rewards { pending_rewards } => pending_rewards.u128(),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+/// ORAI a full withdrawal of `address` would unbond right now.
+fn withdrawable(app: &App, contract: &Addr, address: &str) -> u128 {
+    let msg = QueryMsg::SimulateWithdraw {
+        address: address.to_string(),
+        amount: None,
+        target_tier: None,
+    };
+    match query_answer(app, contract, &msg) {
+        QueryResponse::SimulateWithdraw { amount, .. } => amount.u128(),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+/// Tier, USD deposit and refund reported by the `SimulateDeposit` query.
+fn simulate_deposit(
+    app: &App,
+    contract: &Addr,
+    address: &str,
+    funds: Coin
+) -> AnyResult<(u8, u128, u128)> {
+    let msg = QueryMsg::SimulateDeposit {
+        address: address.to_string(),
+        amount: funds.amount,
+        denom: Some(funds.denom),
+    };
+    match app.wrap().query_wasm_smart(contract, &msg)? {
+        QueryResponse::SimulateDeposit { tier, usd_deposit, refund, .. } =>
+            Ok((tier, usd_deposit.u128(), refund.u128())),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+fn total_delegated(app: &App, contract: &Addr) -> u128 {
+    match query_answer(app, contract, &(QueryMsg::DelegationState {})) {
+        QueryResponse::DelegationState { total_delegated, .. } => total_delegated.u128(),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+fn staked(app: &App, contract: &Addr) -> u128 {
+    app.wrap()
+        .query_all_delegations(contract)
+        .unwrap()
+        .iter()
+        .map(|d| d.amount.amount.u128())
+        .sum()
+}
+
+fn delegation(app: &App, contract: &Addr, validator: &str) -> u128 {
+    app.wrap()
+        .query_delegation(contract, validator)
+        .unwrap()
+        .map(|d| d.amount.amount.u128())
+        .unwrap_or(0)
+}
+
+/// Drops the validator from the active set of the staking module.
+fn jail(app: &mut App, validator: &str) {
+    let key = namespace_with_key(&[b"staking", b"validator_map"], validator.as_bytes());
+    app.init_modules(|_, _, storage| storage.remove(&key));
+}
+
+fn validators_health(app: &App, contract: &Addr) -> Vec<ValidatorHealth> {
+    match query_answer(app, contract, &(QueryMsg::ValidatorsHealth {})) {
+        QueryResponse::ValidatorsHealth { validators } => validators,
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+fn balance(app: &App, address: &str) -> u128 {
+    balance_of(app, address, ORAI)
+}
+
+fn balance_of(app: &App, address: &str, denom: &str) -> u128 {
+    app.wrap().query_balance(address, denom).unwrap().amount.u128()
+}
+
+fn assert_error<T: Debug>(result: AnyResult<T>, message: &str) {
+    let err = result.unwrap_err();
+    assert!(format!("{:?}", err).contains(message), "{:?} does not contain {}", err, message);
+}
+
+#[test]
+fn deposit_takes_what_the_tier_needs() {
+    let (mut app, contract) = setup();
+
+    deposit(&mut app, &contract, ALICE, orai(150), None);
+
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 100, orai(100)));
+    assert_eq!(balance(&app, ALICE), 10_000_000_000 - orai(100));
+    assert_eq!(staked(&app, &contract), orai(100));
+    assert_eq!(total_delegated(&app, &contract), orai(100));
+}
+
+#[test]
+fn deposit_worth_exactly_a_tier() {
+    let (mut app, contract) = setup();
+
+    // The ORAI needed for 100 USD is rounded up by one, the deposit is still taken whole
+    deposit(&mut app, &contract, ALICE, 100_000_000, None);
+
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 100, 100_000_000));
+    assert_eq!(balance(&app, ALICE), 10_000_000_000 - 100_000_000);
+    assert_eq!(staked(&app, &contract), 100_000_000);
+}
+
+#[test]
+fn stable_deposits_count_with_their_decimals() {
+    let (mut app, contract) = setup();
+
+    let funds = [coin(150 * DAI_UNIT, DAI)];
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    app.execute_contract(Addr::unchecked(ALICE), contract.clone(), &msg, &funds).unwrap();
+    let funds = [coin(900_000_000, USDC)];
+    app.execute_contract(Addr::unchecked(BOB), contract.clone(), &msg, &funds).unwrap();
+
+    // Stable coins stay in the contract, the part above the tier is sent back
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 100, 0));
+    assert_eq!(balance_of(&app, contract.as_str(), DAI), 100 * DAI_UNIT);
+    assert_eq!(balance_of(&app, ALICE, DAI), 9_900 * DAI_UNIT);
+    assert_eq!(user_info(&app, &contract, BOB), (2, 900, 0));
+    assert_eq!(balance_of(&app, contract.as_str(), USDC), 900_000_000);
+    assert_eq!(staked(&app, &contract), 0);
+
+    let mut app = mock_app();
+    let mut msg = instantiate_msg();
+    msg.stable_denom = Some(
+        vec![StableDenom {
+            denom: USDC.to_string(),
+            decimals: 19,
+        }]
+    );
+    assert_error(instantiate_tier(&mut app, &msg), "18 decimals");
+}
+
+#[test]
+fn simulated_deposits_match_the_deposit() {
+    let (mut app, contract) = setup_with(|msg| {
+        msg.max_price_deviation = Some(10);
+    });
+
+    let small = coin(orai(50), ORAI);
+    let message = "You should deposit at least 100 USD";
+    assert_error(simulate_deposit(&app, &contract, ALICE, small.clone()), message);
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    let sender = Addr::unchecked(ALICE);
+    assert_error(app.execute_contract(sender.clone(), contract.clone(), &msg, &[small]), message);
+
+    let exact = coin(100_000_000, ORAI);
+    assert_eq!(simulate_deposit(&app, &contract, ALICE, exact.clone()).unwrap(), (3, 100, 0));
+    app.execute_contract(sender.clone(), contract.clone(), &msg, &[exact]).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 100, 100_000_000));
+
+    let stable = coin(950 * DAI_UNIT, DAI);
+    let answer = simulate_deposit(&app, &contract, ALICE, stable.clone()).unwrap();
+    assert_eq!(answer, (1, 1000, 50 * DAI_UNIT));
+    app.execute_contract(sender, contract.clone(), &msg, &[stable]).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE).0, 1);
+    assert_eq!(balance_of(&app, ALICE, DAI), 9_100 * DAI_UNIT);
+
+    // The circuit breaker rejects the simulation like the deposit
+    let msg = ExecuteMsg::ChangePriceOracle {
+        price_oracle: PriceOracleSource::Fixed {
+            exchange_rate: Uint128::new(2_000_000),
+        },
+    };
+    run(&mut app, &contract, ADMIN, &msg, 0).unwrap();
+    let message = "Price moved more than 10%";
+    assert_error(simulate_deposit(&app, &contract, BOB, coin(orai(100), ORAI)), message);
+    assert_error(
+        run(&mut app, &contract, BOB, &(ExecuteMsg::Deposit { lock_duration: None }), orai(100)),
+        message
+    );
+}
+
+#[test]
+fn deposits_are_priced_by_the_configured_oracle() {
+    let (mut app, contract) = setup();
+
+    // The fixed rate of 1 USD per ORAI
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 100, orai(100)));
+
+    let fixed = |exchange_rate: u128| ExecuteMsg::ChangePriceOracle {
+        price_oracle: PriceOracleSource::Fixed {
+            exchange_rate: Uint128::new(exchange_rate),
+        },
+    };
+    assert_error(run(&mut app, &contract, ALICE, &fixed(2_000_000), 0), "Unauthorized");
+    assert_error(run(&mut app, &contract, ADMIN, &fixed(0), 0), "should not be ZERO");
+    let message = "Trying to change to the same price oracle.";
+    assert_error(run(&mut app, &contract, ADMIN, &fixed(1_000_000), 0), message);
+
+    run(&mut app, &contract, ADMIN, &fixed(2_000_000), 0).unwrap();
+    deposit(&mut app, &contract, BOB, 50_000_000, None);
+    assert_eq!(user_info(&app, &contract, BOB), (3, 100, 50_000_000));
+
+    // Band rates have 18 decimals and are as old as their last update
+    let now = app.block_info().time.seconds();
+    use_band(&mut app, &contract, reference_data(4_000_000, now - 30));
+    deposit(&mut app, &contract, CAROL, 225_000_000, None);
+    assert_eq!(user_info(&app, &contract, CAROL), (2, 900, 225_000_000));
+}
+
+#[test]
+fn validators_are_added_weighted_rebalanced_and_removed() {
+    let (mut app, contract) = setup();
+    deposit(&mut app, &contract, ALICE, 1_000_000_000, None);
+    assert_eq!(delegation(&app, &contract, VALIDATORS[0]), 500_000_000);
+    assert_eq!(delegation(&app, &contract, VALIDATORS[1]), 500_000_000);
+
+    let add = |address: &str| ExecuteMsg::AddValidator {
+        address: address.to_string(),
+    };
+    assert_error(run(&mut app, &contract, ALICE, &add(SPARE_VALIDATOR), 0), "Unauthorized");
+    assert_error(run(&mut app, &contract, ADMIN, &add("unknown"), 0), "Validator is not active");
+    let message = "Validator already exists";
+    assert_error(run(&mut app, &contract, ADMIN, &add(VALIDATORS[0]), 0), message);
+    run(&mut app, &contract, ADMIN, &add(SPARE_VALIDATOR), 0).unwrap();
+
+    let weights = |weights: &[(&str, u128)]| ExecuteMsg::SetValidatorWeights {
+        validators: weights
+            .iter()
+            .map(|(address, weight)| ValidatorWithWeight {
+                address: address.to_string(),
+                weight: *weight,
+            })
+            .collect(),
+    };
+    let msg = weights(&[(VALIDATORS[0], 50), (VALIDATORS[1], 40)]);
+    assert_error(run(&mut app, &contract, ADMIN, &msg, 0), "must be 100");
+    let msg = weights(&[(VALIDATORS[0], 50), (VALIDATORS[1], 50)]);
+    assert_error(run(&mut app, &contract, ADMIN, &msg, 0), "exactly the current validators");
+    let msg = weights(&[(VALIDATORS[0], 50), (VALIDATORS[0], 50)]);
+    assert_error(run(&mut app, &contract, ADMIN, &msg, 0), "Duplicated validator address");
+    let msg = weights(&[(VALIDATORS[0], 50), (VALIDATORS[1], 0), (SPARE_VALIDATOR, 50)]);
+    run(&mut app, &contract, ADMIN, &msg, 0).unwrap();
+
+    // New deposits follow the weights, rebalancing moves the existing stake
+    deposit(&mut app, &contract, BOB, 100_000_000, None);
+    assert_eq!(delegation(&app, &contract, SPARE_VALIDATOR), 50_000_000);
+    let message = "Set the validator weight to zero before removing it";
+    let remove = |address: &str| ExecuteMsg::RemoveValidator {
+        address: address.to_string(),
+    };
+    assert_error(run(&mut app, &contract, ADMIN, &remove(VALIDATORS[0]), 0), message);
+    let message = "Validator still has delegations, rebalance first";
+    assert_error(run(&mut app, &contract, ADMIN, &remove(VALIDATORS[1]), 0), message);
+
+    run(&mut app, &contract, CAROL, &(ExecuteMsg::Rebalance {}), 0).unwrap();
+    assert_eq!(delegation(&app, &contract, VALIDATORS[0]), 550_000_000);
+    assert_eq!(delegation(&app, &contract, VALIDATORS[1]), 0);
+    assert_eq!(delegation(&app, &contract, SPARE_VALIDATOR), 550_000_000);
+    let message = "Delegations already match the weights";
+    assert_error(run(&mut app, &contract, CAROL, &(ExecuteMsg::Rebalance {}), 0), message);
+
+    run(&mut app, &contract, ADMIN, &remove(VALIDATORS[1]), 0).unwrap();
+    let message = "Validator does not exist";
+    assert_error(run(&mut app, &contract, ADMIN, &remove(VALIDATORS[1]), 0), message);
+    assert_eq!(total_delegated(&app, &contract), 1_100_000_000);
+}
+
+#[test]
+fn inactive_validators_lose_their_weight() {
+    let (mut app, contract) = setup();
+    deposit(&mut app, &contract, ALICE, 1_000_000_000, None);
+
+    let check = ExecuteMsg::CheckValidators {};
+    assert_error(run(&mut app, &contract, CAROL, &check, 0), "All validators are healthy");
+
+    jail(&mut app, VALIDATORS[1]);
+    let health = validators_health(&app, &contract);
+    assert!(health[0].active && !health[1].active);
+
+    let status = |status: ContractStatus| ExecuteMsg::ChangeStatus { status };
+    run(&mut app, &contract, ADMIN, &status(ContractStatus::Stopped), 0).unwrap();
+    assert_error(run(&mut app, &contract, CAROL, &check, 0), "Contract is not active");
+    run(&mut app, &contract, ADMIN, &status(ContractStatus::Active), 0).unwrap();
+
+    let response = run(&mut app, &contract, CAROL, &check, 0).unwrap();
+    let event = Event::new("wasm-tier_validators_evacuated");
+    let event = event.add_attribute("validator", VALIDATORS[1]);
+    assert!(response.has_event(&event));
+    let weights: Vec<u128> = validators_health(&app, &contract)
+        .iter()
+        .map(|v| v.weight)
+        .collect();
+    assert_eq!(weights, vec![100, 0]);
+
+    assert_error(run(&mut app, &contract, CAROL, &check, 0), "All validators are healthy");
+}
+
+#[test]
+fn rewards_are_harvested_before_new_shares() {
+    let (mut app, contract) = setup();
+
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    advance(&mut app, YEAR);
+
+    // The year of rewards belongs to alice, bob's deposit harvests it before minting
+    deposit(&mut app, &contract, BOB, orai(100), None);
+
+    let alice_rewards = pending_rewards(&app, &contract, ALICE);
+    assert!(alice_rewards.abs_diff(9_000_000) <= 1, "alice earned {}", alice_rewards);
+    assert_eq!(pending_rewards(&app, &contract, BOB), 0);
+    assert_eq!(balance(&app, TREASURY), 1_000_000);
+
+    let alice_balance = balance(&app, ALICE);
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        owner: None,
+    };
+    run(&mut app, &contract, ALICE, &msg, 0).unwrap();
+    assert_eq!(balance(&app, ALICE), alice_balance + alice_rewards);
+    assert_eq!(pending_rewards(&app, &contract, ALICE), 0);
+}
+
+#[test]
+fn rewards_are_harvested_before_they_are_paid() {
+    let (mut app, contract) = setup();
+
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    deposit(&mut app, &contract, BOB, orai(100), None);
+    advance(&mut app, YEAR);
+
+    // Nothing harvested the year of rewards yet, claiming does
+    let alice_balance = balance(&app, ALICE);
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        owner: None,
+    };
+    run(&mut app, &contract, ALICE, &msg, 0).unwrap();
+    let alice_rewards = balance(&app, ALICE) - alice_balance;
+    assert!(alice_rewards.abs_diff(9_000_000) <= 1, "alice earned {}", alice_rewards);
+    assert_eq!(balance(&app, TREASURY), 2_000_000);
+
+    // Withdrawing harvests the second year and pays both years of bob out
+    advance(&mut app, YEAR);
+    let bob_balance = balance(&app, BOB);
+    withdraw(&mut app, &contract, BOB, None, None).unwrap();
+    let bob_rewards = balance(&app, BOB) - bob_balance;
+    assert!(bob_rewards.abs_diff(18_000_000) <= 2, "bob earned {}", bob_rewards);
+
+    run(&mut app, &contract, ALICE, &msg, 0).unwrap();
+    let alice_rewards = balance(&app, ALICE) - alice_balance - alice_rewards;
+    assert!(alice_rewards.abs_diff(9_000_000) <= 1, "alice earned {}", alice_rewards);
+}
+
+#[test]
+fn compounded_rewards_grow_the_deposit() {
+    let (mut app, contract) = setup();
+
+    deposit(&mut app, &contract, ALICE, 900_000_000, None);
+    deposit(&mut app, &contract, BOB, 900_000_000, None);
+
+    let compounding = |enabled: bool, owner: Option<&str>| ExecuteMsg::SetCompounding {
+        enabled,
+        owner: owner.map(str::to_string),
+    };
+    let message = "User has no deposit";
+    assert_error(run(&mut app, &contract, CAROL, &compounding(true, None), 0), message);
+    let msg = compounding(true, Some(ALICE));
+    assert_error(run(&mut app, &contract, BOB, &msg, 0), "Unauthorized");
+    run(&mut app, &contract, ALICE, &compounding(true, None), 0).unwrap();
+
+    let claim = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        owner: None,
+    };
+    let message = "Rewards of this account are compounded";
+    assert_error(run(&mut app, &contract, ALICE, &claim, 0), message);
+
+    // Two years of 81 ORAI after the fee are delegated and lift alice to the top tier
+    advance(&mut app, 2 * YEAR);
+    let refresh = |address: &str| ExecuteMsg::RefreshTier {
+        address: Some(address.to_string()),
+    };
+    run(&mut app, &contract, CAROL, &refresh(ALICE), 0).unwrap();
+    let (tier, usd_deposit, orai_deposit) = user_info(&app, &contract, ALICE);
+    assert_eq!((tier, usd_deposit), (1, 1062));
+    assert!(orai_deposit.abs_diff(1_062_000_000) <= 2, "alice has {}", orai_deposit);
+    assert_eq!(pending_rewards(&app, &contract, ALICE), 0);
+    assert!(total_delegated(&app, &contract).abs_diff(1_962_000_000) <= 2);
+
+    // Bob's rewards stay claimable
+    run(&mut app, &contract, CAROL, &refresh(BOB), 0).unwrap();
+    assert_eq!(user_info(&app, &contract, BOB), (2, 900, 900_000_000));
+    let bob_rewards = pending_rewards(&app, &contract, BOB);
+    assert!(bob_rewards.abs_diff(162_000_000) <= 2, "bob earned {}", bob_rewards);
+}
+
+#[test]
+fn slashing_is_synced_before_shares_are_minted() {
+    let (mut app, contract) = setup();
+
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    let slash = StakingSudo::Slash {
+        validator: VALIDATORS[0].to_string(),
+        percentage: Decimal::percent(10),
+    };
+    app.sudo(SudoMsg::Staking(slash)).unwrap();
+
+    // The simulation sees the slashing before anything synced it
+    let slashed = orai(100) - 5_000_000;
+    assert_eq!(withdrawable(&app, &contract, ALICE), slashed);
+
+    deposit(&mut app, &contract, BOB, orai(100), None);
+
+    assert_eq!(total_delegated(&app, &contract), slashed + orai(100));
+    assert!(withdrawable(&app, &contract, ALICE).abs_diff(slashed) <= 1);
+    assert!(withdrawable(&app, &contract, BOB).abs_diff(orai(100)) <= 1);
+}
+
+#[test]
+fn epochs_are_unbonded_in_batches_and_claimed() {
+    let (mut app, contract) = setup();
+
+    for user in [ALICE, BOB, CAROL] {
+        deposit(&mut app, &contract, user, orai(100), None);
+    }
+    withdraw(&mut app, &contract, ALICE, None, None).unwrap();
+    withdraw(&mut app, &contract, CAROL, None, None).unwrap();
+
+    assert_error(batch_unbond(&mut app, &contract, Some(0)), "Limit should not be ZERO!");
+    assert_error(batch_unbond(&mut app, &contract, None), "lower than 5 days");
+
+    advance(&mut app, BATCH_PERIOD);
+
+    // One entry per transaction, the epoch is undelegated by the first one
+    let response = batch_unbond(&mut app, &contract, Some(1)).unwrap();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("batch_finished", "false")));
+    assert_eq!(staked(&app, &contract), orai(100) + 4);
+
+    match query_answer(&app, &contract, &(QueryMsg::CurrentEpoch {})) {
+        QueryResponse::CurrentEpoch { epoch, pending_batch, .. } => {
+            assert_eq!(epoch.id, 1);
+            assert_eq!(pending_batch, Some(0));
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+
+    let response = batch_unbond(&mut app, &contract, Some(1)).unwrap();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("batch_finished", "true")));
+    assert_error(batch_unbond(&mut app, &contract, None), "Unbond List is Empty!");
+
+    assert_error(claim(&mut app, &contract, ALICE), "Nothing to claim");
+
+    advance(&mut app, UNBOUND_TIME);
+
+    for user in [ALICE, CAROL] {
+        let before = balance(&app, user);
+        claim(&mut app, &contract, user).unwrap();
+        assert_eq!(balance(&app, user), before + orai(100));
+    }
+    assert_error(claim(&mut app, &contract, ALICE), "Nothing to claim");
+
+    // Bob's position is untouched and got the rewards of the closed epoch
+    assert_eq!(user_info(&app, &contract, BOB), (3, 100, orai(100)));
+    assert!(pending_rewards(&app, &contract, BOB) > 0);
+}
+
+#[test]
+fn state_follows_withdrawals_through_their_epoch() {
+    let (mut app, contract) = setup();
+    deposit(&mut app, &contract, ALICE, 1_000_000_000, None);
+    deposit(&mut app, &contract, BOB, 100_000_000, None);
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    let funds = coins(900_000_000, USDC);
+    app.execute_contract(Addr::unchecked(CAROL), contract.clone(), &msg, &funds).unwrap();
+
+    // Queued, unbonding and claimable amounts
+    let state = |app: &App| {
+        match query_answer(app, &contract, &(QueryMsg::State {})) {
+            QueryResponse::State { queued, unbonding, claimable, .. } =>
+                (queued.u128(), unbonding.u128(), claimable.u128()),
+            answer => panic!("Unexpected answer {:?}", answer),
+        }
+    };
+
+    match query_answer(&app, &contract, &(QueryMsg::State {})) {
+        QueryResponse::State { validators, total_delegated, exchange_rate, balance, .. } => {
+            assert_eq!(total_delegated.u128(), 1_100_000_000);
+            for (validator, address) in validators.iter().zip(VALIDATORS) {
+                assert_eq!(validator.address, address);
+                assert_eq!(validator.weight, 50);
+                assert_eq!(validator.target.u128(), 550_000_000);
+                assert_eq!(validator.delegated.u128(), 550_000_000);
+            }
+            assert_eq!(exchange_rate, Some(Uint128::new(1_000_000)));
+            assert_eq!(balance, coins(900_000_000, USDC));
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+    assert_eq!(state(&app), (0, 0, 0));
+
+    withdraw(&mut app, &contract, BOB, None, None).unwrap();
+    assert_eq!(state(&app), (100_000_000, 0, 0));
+    advance(&mut app, BATCH_PERIOD);
+    batch_unbond(&mut app, &contract, None).unwrap();
+    assert_eq!(state(&app), (0, 100_000_000, 0));
+    advance(&mut app, UNBOUND_TIME);
+    assert_eq!(state(&app), (0, 0, 100_000_000));
+    claim(&mut app, &contract, BOB).unwrap();
+    assert_eq!(state(&app), (0, 0, 0));
+
+    // A failing oracle does not fail the query
+    let msg = ExecuteMsg::ChangePriceOracle {
+        price_oracle: PriceOracleSource::Band {
+            contract: "missing".to_string(),
+        },
+    };
+    run(&mut app, &contract, ADMIN, &msg, 0).unwrap();
+    match query_answer(&app, &contract, &(QueryMsg::State {})) {
+        QueryResponse::State { exchange_rate, .. } => assert_eq!(exchange_rate, None),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+#[test]
+fn account_sums_up_deposits_and_withdrawals() {
+    let (mut app, contract) = setup();
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    let funds = coins(100_000_000, USDC);
+    app.execute_contract(Addr::unchecked(ALICE), contract.clone(), &msg, &funds).unwrap();
+    deposit(&mut app, &contract, ALICE, 800_000_000, None);
+
+    let msg = QueryMsg::Account {
+        address: ALICE.to_string(),
+    };
+    let account = |app: &App| query_answer(app, &contract, &msg);
+
+    match account(&app) {
+        QueryResponse::Account {
+            tier,
+            usd_deposit,
+            orai_deposit,
+            stable_deposits,
+            expires_at,
+            lifetime_deposits,
+            lifetime_usd_deposit,
+            queued,
+            ..
+        } => {
+            assert_eq!((tier, usd_deposit.u128(), orai_deposit.u128()), (2, 900, 800_000_000));
+            assert_eq!(stable_deposits, coins(100_000_000, USDC));
+            assert_eq!(expires_at, None);
+            let lifetime = vec![coin(100_000_000, USDC), coin(800_000_000, ORAI)];
+            assert_eq!(lifetime_deposits, lifetime);
+            assert_eq!(lifetime_usd_deposit.u128(), 900);
+            assert_eq!(queued.u128(), 0);
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+
+    withdraw(&mut app, &contract, ALICE, Some(400_000_000), None).unwrap();
+    match account(&app) {
+        QueryResponse::Account { tier, usd_deposit, queued, lifetime_usd_deposit, .. } => {
+            assert_eq!((tier, usd_deposit.u128(), queued.u128()), (3, 500, 400_000_000));
+            assert_eq!(lifetime_usd_deposit.u128(), 900);
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+
+    advance(&mut app, BATCH_PERIOD);
+    batch_unbond(&mut app, &contract, None).unwrap();
+    let claim_time = app.block_info().time.seconds() + UNBOUND_TIME;
+    match account(&app) {
+        QueryResponse::Account { queued, unbonding, claimable, pending_rewards, .. } => {
+            assert_eq!(queued.u128(), 0);
+            assert_eq!(unbonding.len(), 1);
+            assert_eq!(unbonding[0].amount.u128(), 400_000_000);
+            assert_eq!(unbonding[0].claim_time, claim_time);
+            assert_eq!(claimable.u128(), 0);
+            assert!(pending_rewards.u128() > 0);
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+
+    advance(&mut app, UNBOUND_TIME);
+    match account(&app) {
+        QueryResponse::Account { unbonding, claimable, .. } => {
+            assert!(unbonding.is_empty());
+            assert_eq!(claimable.u128(), 400_000_000);
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+#[test]
+fn refreshed_tiers_follow_the_tier_policy() {
+    let (mut app, contract) = setup();
+    deposit(&mut app, &contract, ALICE, 1_000_000_000, None);
+    deposit(&mut app, &contract, BOB, 900_000_000, None);
+
+    let price = |exchange_rate: u128| ExecuteMsg::ChangePriceOracle {
+        price_oracle: PriceOracleSource::Fixed {
+            exchange_rate: Uint128::new(exchange_rate),
+        },
+    };
+    let policy = |tier_policy: TierPolicy| ExecuteMsg::ChangeTierPolicy { tier_policy };
+    let refresh = |address: &str| ExecuteMsg::RefreshTier {
+        address: Some(address.to_string()),
+    };
+
+    // Upgrades apply right away, sticky tiers are never lowered
+    run(&mut app, &contract, ADMIN, &price(1_200_000), 0).unwrap();
+    run(&mut app, &contract, CAROL, &refresh(BOB), 0).unwrap();
+    assert_eq!(user_info(&app, &contract, BOB), (1, 1080, 900_000_000));
+    run(&mut app, &contract, ADMIN, &price(500_000), 0).unwrap();
+    run(&mut app, &contract, CAROL, &refresh(ALICE), 0).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE).0, 1);
+
+    let msg = policy(TierPolicy::GracePeriod { seconds: 600 });
+    assert_error(run(&mut app, &contract, ALICE, &msg, 0), "Unauthorized");
+    run(&mut app, &contract, ADMIN, &msg, 0).unwrap();
+    run(&mut app, &contract, CAROL, &refresh(ALICE), 0).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE).0, 1);
+    advance(&mut app, 600);
+    run(&mut app, &contract, CAROL, &refresh(ALICE), 0).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 500, 1_000_000_000));
+
+    run(&mut app, &contract, ADMIN, &policy(TierPolicy::RefreshOnTouch {}), 0).unwrap();
+    run(&mut app, &contract, CAROL, &refresh(BOB), 0).unwrap();
+    assert_eq!(user_info(&app, &contract, BOB), (3, 450, 900_000_000));
+}
+
+#[test]
+fn stable_deposits_stay_with_a_partial_withdrawal() {
+    let (mut app, contract) = setup();
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    let funds = coins(900_000_000, USDC);
+    app.execute_contract(Addr::unchecked(ALICE), contract.clone(), &msg, &funds).unwrap();
+    deposit(&mut app, &contract, ALICE, 100_000_000, None);
+    assert_eq!(user_info(&app, &contract, ALICE), (1, 1000, 100_000_000));
+
+    let message = "Stable deposits already cover a higher tier than the target tier";
+    assert_error(withdraw(&mut app, &contract, ALICE, None, Some(3)), message);
+
+    withdraw(&mut app, &contract, ALICE, None, Some(2)).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE), (2, 900, 0));
+    assert_eq!(balance_of(&app, ALICE, USDC), 9_100_000_000);
+}
+
+#[test]
+fn tiers_expire_unless_renewed() {
+    let (mut app, contract) = setup_with(|msg| {
+        msg.tier_validity = Some(YEAR);
+    });
+    let start = app.block_info().time.seconds();
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+
+    let renew = |owner: Option<&str>| ExecuteMsg::Renew {
+        owner: owner.map(str::to_string),
+    };
+    assert_error(run(&mut app, &contract, BOB, &renew(None), 0), "User has no deposit");
+    assert_error(run(&mut app, &contract, BOB, &renew(Some(ALICE)), 0), "Unauthorized");
+
+    advance(&mut app, YEAR - 1);
+    run(&mut app, &contract, ALICE, &renew(None), 0).unwrap();
+    advance(&mut app, YEAR - 1);
+    assert_eq!(user_info(&app, &contract, ALICE).0, 3);
+
+    // An expired tier counts as the minimal one, also in the history, until it is renewed
+    advance(&mut app, 1);
+    let expired_at = app.block_info().time.seconds();
+    assert_eq!(user_info(&app, &contract, ALICE), (4, 100, orai(100)));
+    assert_eq!(tier_at(&app, &contract, ALICE, expired_at - 1), 3);
+    assert_eq!(tier_at(&app, &contract, ALICE, expired_at), 4);
+
+    advance(&mut app, 60);
+    let response = run(&mut app, &contract, ALICE, &renew(None), 0).unwrap();
+    let expires_at = (app.block_info().time.seconds() + YEAR).to_string();
+    assert!(response.has_event(&Event::new("wasm").add_attribute("expires_at", expires_at)));
+    assert_eq!(user_info(&app, &contract, ALICE).0, 3);
+    assert_eq!(tier_at(&app, &contract, ALICE, expired_at + 30), 4);
+    assert_eq!(tier_held(&app, &contract, ALICE, start, app.block_info().time.seconds()), 4);
+
+    let validity = |tier_validity: Option<u64>| ExecuteMsg::ChangeTierValidity { tier_validity };
+    let message = "Tier validity should not be ZERO!";
+    assert_error(run(&mut app, &contract, ADMIN, &validity(Some(0)), 0), message);
+    assert_error(run(&mut app, &contract, ALICE, &validity(None), 0), "Unauthorized");
+    run(&mut app, &contract, ADMIN, &validity(None), 0).unwrap();
+    advance(&mut app, 2 * YEAR);
+    assert_eq!(user_info(&app, &contract, ALICE).0, 3);
+}
+
+#[test]
+fn funded_deposits_need_consent_and_end_with_the_deposit() {
+    let (mut app, contract) = setup();
+
+    let deposit_for = |beneficiary: &str, funder_withdraws: bool| ExecuteMsg::DepositFor {
+        beneficiary: beneficiary.to_string(),
+        funder_withdraws,
+        lock_duration: None,
+    };
+    let funded = |app: &App, funder: &str| {
+        let msg = QueryMsg::FundedDeposits {
+            funder: funder.to_string(),
+            start_after: None,
+            limit: None,
+        };
+        match query_answer(app, &contract, &msg) {
+            QueryResponse::FundedDeposits { deposits } =>
+                deposits
+                    .into_iter()
+                    .map(|d| d.beneficiary)
+                    .collect::<Vec<_>>(),
+            answer => panic!("Unexpected answer {:?}", answer),
+        }
+    };
+
+    // Keeping the right to withdraw needs the approval of the beneficiary
+    let msg = deposit_for(BOB, true);
+    assert_error(run(&mut app, &contract, ALICE, &msg, orai(100)), "Unauthorized");
+    let approve = ExecuteMsg::Approve {
+        operator: ALICE.to_string(),
+        permissions: vec![Permission::Withdraw],
+        expires: None,
+    };
+    run(&mut app, &contract, BOB, &approve, 0).unwrap();
+    run(&mut app, &contract, ALICE, &msg, orai(100)).unwrap();
+    assert_eq!(user_info(&app, &contract, BOB), (3, 100, orai(100)));
+    assert_eq!(funded(&app, ALICE), vec![BOB.to_string()]);
+
+    let message = "The deposit of bob is withdrawn by another address";
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    assert_error(run(&mut app, &contract, BOB, &msg, orai(900)), message);
+    assert_error(run(&mut app, &contract, CAROL, &deposit_for(BOB, false), orai(900)), message);
+    let withdraw_bob = ExecuteMsg::Withdraw {
+        amount: None,
+        target_tier: None,
+        owner: Some(BOB.to_string()),
+    };
+    assert_error(withdraw(&mut app, &contract, BOB, None, None), "Unauthorized");
+
+    // Once the funder took the deposit back the beneficiary starts over
+    run(&mut app, &contract, ALICE, &withdraw_bob, 0).unwrap();
+    assert_eq!(user_info(&app, &contract, BOB), (4, 0, 0));
+    assert!(funded(&app, ALICE).is_empty());
+    deposit(&mut app, &contract, BOB, orai(100), None);
+    assert_eq!(user_info(&app, &contract, BOB), (3, 100, orai(100)));
+
+    // A gift belongs to the beneficiary and ends with its withdrawal
+    run(&mut app, &contract, CAROL, &deposit_for(BOB, false), orai(800)).unwrap();
+    assert_eq!(user_info(&app, &contract, BOB).0, 2);
+    assert_eq!(funded(&app, CAROL), vec![BOB.to_string()]);
+    withdraw(&mut app, &contract, BOB, None, None).unwrap();
+    assert!(funded(&app, CAROL).is_empty());
+}
+
+#[test]
+fn operators_act_within_their_approval() {
+    let (mut app, contract) = setup();
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+
+    let now = app.block_info().time.seconds();
+    let approve = |operator: &str, permissions: Vec<Permission>, expires: Option<u64>| {
+        ExecuteMsg::Approve {
+            operator: operator.to_string(),
+            permissions,
+            expires,
+        }
+    };
+    let msg = approve(ALICE, vec![Permission::Claim], None);
+    assert_error(run(&mut app, &contract, ALICE, &msg, 0), "Cannot approve yourself");
+    let msg = approve(BOB, vec![], None);
+    assert_error(run(&mut app, &contract, ALICE, &msg, 0), "Permissions list is empty");
+    let msg = approve(BOB, vec![Permission::Claim], Some(now));
+    assert_error(run(&mut app, &contract, ALICE, &msg, 0), "Approval is already expired");
+
+    let msg = approve(BOB, vec![Permission::Claim, Permission::Refresh], Some(now + YEAR));
+    run(&mut app, &contract, ALICE, &msg, 0).unwrap();
+    let approval = QueryMsg::Approval {
+        owner: ALICE.to_string(),
+        operator: BOB.to_string(),
+    };
+    match query_answer(&app, &contract, &approval) {
+        QueryResponse::Approval { approval: Some(approval) } => {
+            assert_eq!(approval.permissions, vec![Permission::Claim, Permission::Refresh]);
+            assert_eq!(approval.expires, Some(now + YEAR));
+        }
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+
+    let withdraw_alice = ExecuteMsg::Withdraw {
+        amount: None,
+        target_tier: None,
+        owner: Some(ALICE.to_string()),
+    };
+    assert_error(run(&mut app, &contract, BOB, &withdraw_alice, 0), "Unauthorized");
+
+    // Claimed rewards always go to the owner
+    advance(&mut app, YEAR / 2);
+    let claim = |recipient: Option<&str>| ExecuteMsg::ClaimRewards {
+        recipient: recipient.map(str::to_string),
+        owner: Some(ALICE.to_string()),
+    };
+    let message = "Operators cannot choose the recipient";
+    assert_error(run(&mut app, &contract, BOB, &claim(Some(BOB)), 0), message);
+    let (alice_balance, bob_balance) = (balance(&app, ALICE), balance(&app, BOB));
+    run(&mut app, &contract, BOB, &claim(None), 0).unwrap();
+    assert!(balance(&app, ALICE) > alice_balance);
+    assert_eq!(balance(&app, BOB), bob_balance);
+
+    let renew = ExecuteMsg::Renew {
+        owner: Some(ALICE.to_string()),
+    };
+    run(&mut app, &contract, BOB, &renew, 0).unwrap();
+
+    advance(&mut app, YEAR / 2);
+    assert_error(run(&mut app, &contract, BOB, &claim(None), 0), "Unauthorized");
+
+    let revoke = |operator: &str| ExecuteMsg::Revoke {
+        operator: operator.to_string(),
+    };
+    assert_error(run(&mut app, &contract, ALICE, &revoke(CAROL), 0), "Approval not found");
+    run(&mut app, &contract, ALICE, &revoke(BOB), 0).unwrap();
+    match query_answer(&app, &contract, &approval) {
+        QueryResponse::Approval { approval } => assert!(approval.is_none()),
+        answer => panic!("Unexpected answer {:?}", answer),
+    }
+}
+
+#[test]
+fn failing_hooks_do_not_revert_tier_changes() {
+    let (mut app, contract) = setup();
+    let hook = add_hook(&mut app, &contract, false);
+
+    let add = ExecuteMsg::AddHook {
+        contract_addr: hook.to_string(),
+    };
+    assert_error(run(&mut app, &contract, ALICE, &add, 0), "Unauthorized");
+    assert_error(run(&mut app, &contract, ADMIN, &add, 0), "Hook is already registered");
+
+    let broken_hook = add_hook(&mut app, &contract, true);
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    let response = run(&mut app, &contract, ALICE, &msg, orai(100)).unwrap();
+    let event = Event::new("wasm").add_attribute("action", "hook_failed");
+    assert!(response.has_event(&event));
+    assert_eq!(user_info(&app, &contract, ALICE).0, 3);
+
+    // Only tier changes are sent
+    deposit(&mut app, &contract, ALICE, orai(800), None);
+    let refresh = ExecuteMsg::RefreshTier { address: None };
+    run(&mut app, &contract, ALICE, &refresh, 0).unwrap();
+    withdraw(&mut app, &contract, ALICE, None, None).unwrap();
+    let changes: Vec<(String, u8, u8)> = app
+        .wrap()
+        .query_wasm_smart(&hook, &Empty {})
+        .unwrap();
+    let alice = ALICE.to_string();
+    assert_eq!(changes, vec![(alice.clone(), 4, 3), (alice.clone(), 3, 2), (alice, 2, 4)]);
+
+    let remove = |hook: &Addr| ExecuteMsg::RemoveHook {
+        contract_addr: hook.to_string(),
+    };
+    run(&mut app, &contract, ADMIN, &remove(&broken_hook), 0).unwrap();
+    assert_error(run(&mut app, &contract, ADMIN, &remove(&broken_hook), 0), "Hook not found");
+    let response = run(&mut app, &contract, BOB, &msg, orai(100)).unwrap();
+    assert!(!response.has_event(&event));
+}
+
+#[test]
+fn users_are_listed_with_their_current_tier() {
+    let (mut app, contract) = setup_with(|msg| {
+        msg.tier_validity = Some(YEAR);
+    });
+    deposit(&mut app, &contract, ALICE, orai(700), Some(LOCK_DURATION));
+    deposit(&mut app, &contract, BOB, orai(100), None);
+    deposit(&mut app, &contract, CAROL, orai(900), None);
+
+    let all_users = |app: &App, tier: Option<u8>, start_after: Option<&str>| {
+        let msg = QueryMsg::AllUsers {
+            start_after: start_after.map(str::to_string),
+            limit: Some(2),
+            tier,
+        };
+        match query_answer(app, &contract, &msg) {
+            QueryResponse::AllUsers { users } =>
+                users
+                    .into_iter()
+                    .map(|u| (u.address, u.tier))
+                    .collect::<Vec<_>>(),
+            answer => panic!("Unexpected answer {:?}", answer),
+        }
+    };
+    let tier_stats = |app: &App| {
+        match query_answer(app, &contract, &(QueryMsg::TierStats {})) {
+            QueryResponse::TierStats { tiers } =>
+                tiers
+                    .into_iter()
+                    .map(|s| (s.tier, s.holders, s.usd_deposit.u128()))
+                    .collect::<Vec<_>>(),
+            answer => panic!("Unexpected answer {:?}", answer),
+        }
+    };
+    let (alice, bob, carol) = (ALICE.to_string(), BOB.to_string(), CAROL.to_string());
+
+    assert_eq!(all_users(&app, None, None), vec![(alice.clone(), 1), (bob.clone(), 3)]);
+    assert_eq!(all_users(&app, None, Some(BOB)), vec![(carol.clone(), 2)]);
+    assert_eq!(all_users(&app, Some(2), None), vec![(carol.clone(), 2)]);
+    assert_eq!(tier_stats(&app), vec![(1, 1, 667), (2, 1, 900), (3, 1, 100), (4, 0, 0)]);
+
+    // The ended lock shows right away, the stats follow once the deposit is refreshed
+    advance(&mut app, LOCK_DURATION);
+    assert_eq!(all_users(&app, None, None), vec![(alice.clone(), 3), (bob.clone(), 3)]);
+    assert_eq!(all_users(&app, Some(1), None), vec![(alice.clone(), 3)]);
+    let refresh = ExecuteMsg::RefreshTier { address: Some(alice.clone()) };
+    run(&mut app, &contract, BOB, &refresh, 0).unwrap();
+    assert_eq!(all_users(&app, Some(3), None), vec![(alice.clone(), 3), (bob.clone(), 3)]);
+    assert_eq!(tier_stats(&app), vec![(1, 0, 0), (2, 1, 900), (3, 2, 767), (4, 0, 0)]);
+
+    advance(&mut app, YEAR - LOCK_DURATION);
+    assert_eq!(all_users(&app, None, Some(BOB)), vec![(carol, 4)]);
+    assert_eq!(tier_stats(&app)[1], (2, 1, 900));
+}
+
+#[test]
+fn partial_withdrawal_after_the_lock_ends() {
+    let (mut app, contract) = setup();
+    let start = app.block_info().time.seconds();
+    let lock_end = start + LOCK_DURATION;
+
+    assert_eq!(tier_at(&app, &contract, ALICE, start), 4);
+
+    // 667 USD count as 1000 while locked
+    deposit(&mut app, &contract, ALICE, orai(700), Some(LOCK_DURATION));
+    assert_eq!(user_info(&app, &contract, ALICE), (1, 667, orai(667)));
+    assert_error(withdraw(&mut app, &contract, ALICE, Some(1), None), "Deposit is locked");
+
+    advance(&mut app, LOCK_DURATION);
+
+    // The boosted tier ends with the lock, in the current tier and in the history
+    assert_eq!(user_info(&app, &contract, ALICE).0, 3);
+    assert_eq!(tier_at(&app, &contract, ALICE, lock_end - 1), 1);
+    assert_eq!(tier_at(&app, &contract, ALICE, lock_end), 3);
+
+    assert_error(
+        withdraw(&mut app, &contract, ALICE, None, Some(2)),
+        "Target tier must be lower than the current one"
+    );
+    assert_error(
+        withdraw(&mut app, &contract, ALICE, Some(orai(667) + 1), None),
+        "Withdraw amount must be from 1 to the ORAI deposit"
+    );
+
+    withdraw(&mut app, &contract, ALICE, Some(500_000_000), None).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 168, orai(667) - 500_000_000));
+    assert_eq!(total_delegated(&app, &contract), orai(667) - 500_000_000);
+
+    // Dropping to the minimal tier leaves nothing and closes the position
+    withdraw(&mut app, &contract, ALICE, None, Some(4)).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE), (4, 0, 0));
+    assert_eq!(tier_at(&app, &contract, ALICE, app.block_info().time.seconds()), 4);
+}
+
+#[test]
+fn a_linked_wallet_takes_the_tier_from_its_owner() {
+    let (mut app, contract) = setup();
+    let before_link = app.block_info().time.seconds();
+
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    advance(&mut app, 60);
+    let msg = ExecuteMsg::ProposeLink {
+        address: BOB.to_string(),
+    };
+    run(&mut app, &contract, ALICE, &msg, 0).unwrap();
+    let msg = ExecuteMsg::AcceptLink {
+        owner: ALICE.to_string(),
+    };
+    run(&mut app, &contract, BOB, &msg, 0).unwrap();
+    let linked_at = app.block_info().time.seconds();
+
+    // Only one of the two addresses holds the tier, now and in the history
+    assert_eq!(user_info(&app, &contract, ALICE).0, 4);
+    assert_eq!(user_info(&app, &contract, BOB).0, 3);
+    assert_eq!(tier_at(&app, &contract, ALICE, linked_at), 4);
+    assert_eq!(tier_at(&app, &contract, BOB, linked_at), 3);
+    assert_eq!(tier_at(&app, &contract, ALICE, before_link), 3);
+    assert_eq!(tier_at(&app, &contract, BOB, before_link), 4);
+
+    // A deposit of the owner upgrades the linked address
+    advance(&mut app, 60);
+    deposit(&mut app, &contract, ALICE, orai(900), None);
+    let now = app.block_info().time.seconds();
+    assert_eq!(user_info(&app, &contract, ALICE).0, 4);
+    assert_eq!(user_info(&app, &contract, BOB).0, 1);
+    assert_eq!((tier_at(&app, &contract, ALICE, now), tier_at(&app, &contract, BOB, now)), (4, 1));
+
+    advance(&mut app, 60);
+    run(&mut app, &contract, BOB, &(ExecuteMsg::Unlink {}), 0).unwrap();
+    let unlinked_at = app.block_info().time.seconds();
+    assert_eq!(user_info(&app, &contract, ALICE).0, 1);
+    assert_eq!(user_info(&app, &contract, BOB).0, 4);
+    assert_eq!(tier_at(&app, &contract, ALICE, unlinked_at), 1);
+    assert_eq!(tier_at(&app, &contract, BOB, unlinked_at), 4);
+    assert_eq!(tier_at(&app, &contract, ALICE, now), 4);
+    assert_eq!(tier_at(&app, &contract, BOB, now), 1);
+}
+
+#[test]
+fn tier_held_is_the_worst_tier_of_the_window() {
+    let (mut app, contract) = setup();
+    let start = app.block_info().time.seconds();
+
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    deposit(&mut app, &contract, BOB, orai(700), Some(LOCK_DURATION));
+    advance(&mut app, 10);
+    withdraw(&mut app, &contract, ALICE, None, None).unwrap();
+    advance(&mut app, 10);
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+
+    // Nothing before the window start counts as the minimal tier
+    assert_eq!(tier_held(&app, &contract, ALICE, start - 1, start + 5), 4);
+    assert_eq!(tier_held(&app, &contract, ALICE, start, start + 5), 3);
+    // Withdrawing and coming back inside the window does not keep the tier
+    assert_eq!(tier_held(&app, &contract, ALICE, start, start + 30), 4);
+    assert_eq!(tier_held(&app, &contract, ALICE, start + 20, start + 30), 3);
+
+    // The boosted tier is held until the lock ends
+    let lock_end = start + LOCK_DURATION;
+    assert_eq!(tier_held(&app, &contract, BOB, start, lock_end - 1), 1);
+    assert_eq!(tier_held(&app, &contract, BOB, start, lock_end), 3);
+}
+
+#[test]
+fn twap_needs_recent_observations() {
+    let (mut app, contract) = setup_with(|msg| {
+        msg.twap_window = Some(1200);
+    });
+    let record_price = ExecuteMsg::RecordPrice {};
+
+    assert_error(
+        run(&mut app, &contract, ALICE, &(ExecuteMsg::Deposit { lock_duration: None }), orai(100)),
+        "Not enough price observations"
+    );
+
+    for _ in 0..20 {
+        run(&mut app, &contract, CAROL, &record_price, 0).unwrap();
+        assert_error(run(&mut app, &contract, CAROL, &record_price, 0), "recorded too recently");
+        advance(&mut app, 60);
+    }
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+    assert_eq!(user_info(&app, &contract, ALICE), (3, 100, orai(100)));
+
+    // A sample taken at three times the price only weighs five minutes of the window, so
+    // the average is 5/3 USD instead of 2 USD
+    let msg = ExecuteMsg::ChangePriceOracle {
+        price_oracle: PriceOracleSource::Fixed {
+            exchange_rate: Uint128::new(3_000_000),
+        },
+    };
+    run(&mut app, &contract, ADMIN, &msg, 0).unwrap();
+    run(&mut app, &contract, CAROL, &record_price, 0).unwrap();
+    advance(&mut app, 600);
+    deposit(&mut app, &contract, BOB, orai(300), None);
+    assert_eq!(user_info(&app, &contract, BOB), (3, 100, 60_000_025));
+
+    // Once keepers are idle the samples no longer cover the window
+    advance(&mut app, 3600);
+    assert_error(
+        run(&mut app, &contract, CAROL, &(ExecuteMsg::Deposit { lock_duration: None }), orai(300)),
+        "cover too little of the TWAP window"
+    );
+}
+
+#[test]
+fn circuit_breaker_limits_the_drift_per_period() {
+    let (mut app, contract) = setup_with(|msg| {
+        msg.max_price_deviation = Some(10);
+        msg.max_price_age = Some(600);
+    });
+    let now = app.block_info().time.seconds();
+    let band = use_band(&mut app, &contract, reference_data(1_000_000, now));
+    deposit(&mut app, &contract, ALICE, orai(100), None);
+
+    advance(&mut app, 60);
+    let now = app.block_info().time.seconds();
+    set_band_price(&mut app, &band, reference_data(1_090_000, now));
+    deposit(&mut app, &contract, BOB, orai(100), None);
+
+    // Small steps do not move the baseline within a period, not even through keepers
+    advance(&mut app, 60);
+    let now = app.block_info().time.seconds();
+    set_band_price(&mut app, &band, reference_data(1_180_000, now));
+    let msg = ExecuteMsg::Deposit { lock_duration: None };
+    let message = "Price moved more than 10% from the last accepted rate";
+    assert_error(run(&mut app, &contract, CAROL, &msg, orai(200)), message);
+    run(&mut app, &contract, CAROL, &(ExecuteMsg::RecordPrice {}), 0).unwrap();
+    assert_error(run(&mut app, &contract, CAROL, &msg, orai(200)), message);
+
+    // A period later keepers move the baseline by at most 10%, to 1.1 USD
+    advance(&mut app, 24 * 60 * 60);
+    let now = app.block_info().time.seconds();
+    set_band_price(&mut app, &band, reference_data(1_180_000, now));
+    run(&mut app, &contract, CAROL, &(ExecuteMsg::RecordPrice {}), 0).unwrap();
+    run(&mut app, &contract, CAROL, &msg, orai(200)).unwrap();
+    assert_eq!(user_info(&app, &contract, CAROL).0, 3);
+
+    // Stale quotes are rejected
+    set_band_price(&mut app, &band, reference_data(1_180_000, now - 601));
+    assert_error(run(&mut app, &contract, ALICE, &msg, orai(900)), "Price quote is too old");
+
+    // The admin can accept any rate
+    set_band_price(&mut app, &band, reference_data(2_000_000, now));
+    assert_error(run(&mut app, &contract, ALICE, &msg, orai(900)), message);
+    let reset = ExecuteMsg::ResetPriceBaseline { exchange_rate: None };
+    assert_error(run(&mut app, &contract, ALICE, &reset, 0), "Unauthorized");
+    run(&mut app, &contract, ADMIN, &reset, 0).unwrap();
+    run(&mut app, &contract, ALICE, &msg, orai(900)).unwrap();
+    assert_eq!(user_info(&app, &contract, ALICE).0, 1);
+}
+
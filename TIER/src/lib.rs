@@ -6,4 +6,7 @@ pub mod msg;
 pub mod state;
 pub mod utils;
 
+#[cfg(test)]
+mod integration_tests;
+
 pub use crate::error::ContractError;
@@ -1,4 +1,4 @@
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{ Coin, Uint128 };
 use schemars::JsonSchema;
 use serde::{ Deserialize, Serialize };
 
@@ -36,7 +36,16 @@ pub struct InstantiateMsg {
     pub validators: Vec<ValidatorWithWeight>,
     pub deposits: Vec<Uint128>,
     pub oraiswap_contract: OraiswapContract,
-    pub stable_denom: Option<Vec<String>>,
+    pub stable_denom: Option<Vec<StableDenom>>,
+    pub price_oracle: Option<PriceOracleSource>,
+    pub twap_window: Option<u64>,
+    pub max_price_deviation: Option<u128>,
+    pub max_price_age: Option<u64>,
+    pub reward_fee: Option<u128>,
+    pub treasury: Option<String>,
+    pub tier_policy: Option<TierPolicy>,
+    pub lock_boosts: Option<Vec<LockBoost>>,
+    pub tier_validity: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -52,17 +61,102 @@ pub enum ExecuteMsg {
         oraiswap_router_contract: String,
         usdt_contract: String,
     },
-    Deposit {},
-    Withdraw {},
-    BatchUnbond {},
+    ChangePriceOracle {
+        price_oracle: PriceOracleSource,
+    },
+    ChangeTwapWindow {
+        twap_window: u64,
+    },
+    RecordPrice {},
+    ChangePriceLimits {
+        max_price_deviation: Option<u128>,
+        max_price_age: Option<u64>,
+    },
+    ResetPriceBaseline {
+        exchange_rate: Option<Uint128>,
+    },
+    ChangeRewardFee {
+        reward_fee: u128,
+        treasury: Option<String>,
+    },
+    ChangeTierPolicy {
+        tier_policy: TierPolicy,
+    },
+    ChangeLockBoosts {
+        lock_boosts: Vec<LockBoost>,
+    },
+    ChangeTierValidity {
+        tier_validity: Option<u64>,
+    },
+    AddHook {
+        contract_addr: String,
+    },
+    RemoveHook {
+        contract_addr: String,
+    },
+    Deposit {
+        lock_duration: Option<u64>,
+    },
+    DepositFor {
+        beneficiary: String,
+        funder_withdraws: bool,
+        lock_duration: Option<u64>,
+    },
+    Withdraw {
+        amount: Option<Uint128>,
+        target_tier: Option<u8>,
+        owner: Option<String>,
+    },
+    Approve {
+        operator: String,
+        permissions: Vec<Permission>,
+        expires: Option<u64>,
+    },
+    Revoke {
+        operator: String,
+    },
+    ProposeLink {
+        address: String,
+    },
+    AcceptLink {
+        owner: String,
+    },
+    Unlink {},
+    BatchUnbond {
+        limit: Option<u32>,
+    },
     Claim {
         recipient: Option<String>,
         start: Option<u32>,
         limit: Option<u32>,
+        owner: Option<String>,
     },
-    WithdrawRewards {
+    HarvestRewards {},
+    ClaimRewards {
         recipient: Option<String>,
+        owner: Option<String>,
+    },
+    SetCompounding {
+        enabled: bool,
+        owner: Option<String>,
+    },
+    RefreshTier {
+        address: Option<String>,
     },
+    Renew {
+        owner: Option<String>,
+    },
+    AddValidator {
+        address: String,
+    },
+    RemoveValidator {
+        address: String,
+    },
+    SetValidatorWeights {
+        validators: Vec<ValidatorWithWeight>,
+    },
+    Rebalance {},
+    CheckValidators {},
     Redelegate {
         new_validator_address: String,
         old_validator_address: String,
@@ -93,20 +187,54 @@ pub enum ExecuteResponse {
         amount: Uint128,
         status: ResponseStatus,
     },
-    WithdrawRewards {
+    HarvestRewards {
+        amount: Uint128,
+        fee: Uint128,
+        status: ResponseStatus,
+    },
+    ClaimRewards {
         amount: Uint128,
         status: ResponseStatus,
     },
+    RefreshTier {
+        usd_deposit: Uint128,
+        orai_deposit: Uint128,
+        tier: u8,
+        status: ResponseStatus,
+    },
     Redelegate {
         amount: Uint128,
         status: ResponseStatus,
     },
+    RecordPrice {
+        exchange_rate: Uint128,
+        status: ResponseStatus,
+    },
+}
+
+/// Sent to every registered hook contract when the tier of an address changes.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TierHookMsg {
+    TierChanged {
+        address: String,
+        old_tier: u8,
+        new_tier: u8,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
+    Hooks {},
+    AllUsers {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        tier: Option<u8>,
+    },
+    TierStats {},
+    State {},
     UserInfo {
         address: String,
     },
@@ -119,6 +247,62 @@ pub enum QueryMsg {
         limit: Option<u32>,
     },
     Unbonds {},
+    CurrentEpoch {},
+    Epochs {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    UserEpochs {
+        address: String,
+    },
+    FundedDeposits {
+        funder: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    Approval {
+        owner: String,
+        operator: String,
+    },
+    Approvals {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    Link {
+        address: String,
+    },
+    PriceObservations {},
+    DelegationState {},
+    ValidatorsHealth {},
+    Rewards {
+        address: String,
+    },
+    PreviewTier {
+        address: String,
+    },
+    SimulateDeposit {
+        address: String,
+        amount: Uint128,
+        denom: Option<String>,
+    },
+    Account {
+        address: String,
+    },
+    SimulateWithdraw {
+        address: String,
+        amount: Option<Uint128>,
+        target_tier: Option<u8>,
+    },
+    TierAt {
+        address: String,
+        timestamp: u64,
+    },
+    TierHeld {
+        address: String,
+        start: u64,
+        end: u64,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -127,6 +311,63 @@ pub struct SerializedWithdrawals {
     pub amount: Uint128,
     pub claim_time: u64,
     pub timestamp: u64,
+    pub epoch_id: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedEpoch {
+    pub id: u64,
+    pub amount: Uint128,
+    pub start_time: u64,
+    pub close_time: Option<u64>,
+    pub claim_time: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedUserEpoch {
+    pub epoch_id: u64,
+    pub amount: Uint128,
+    pub close_time: Option<u64>,
+    pub claim_time: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedUserInfo {
+    pub address: String,
+    pub tier: u8,
+    pub timestamp: u64,
+    pub usd_deposit: Uint128,
+    pub orai_deposit: Uint128,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedTierStats {
+    pub tier: u8,
+    pub holders: u64,
+    pub usd_deposit: Uint128,
+    pub orai_deposit: Uint128,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedApproval {
+    pub operator: String,
+    pub permissions: Vec<Permission>,
+    pub expires: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedFundedDeposit {
+    pub beneficiary: String,
+    pub deposits: Vec<Coin>,
+    pub usd_deposit: Uint128,
+    pub funder_withdraws: bool,
+    pub timestamp: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -137,6 +378,14 @@ pub struct SerializedUnbonds {
     pub timestamp: u64,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SerializedPriceObservation {
+    pub exchange_rate: Uint128,
+    pub timestamp: u64,
+}
+
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryResponse {
@@ -147,13 +396,28 @@ pub enum QueryResponse {
         usd_deposits: Vec<Uint128>,
         min_tier: u8,
         oraiswap_contract: OraiswapContract,
-        stable_denom: Vec<String>,
+        stable_denom: Vec<StableDenom>,
+        price_oracle: PriceOracleSource,
+        twap_window: u64,
+        max_price_deviation: Option<u128>,
+        max_price_age: Option<u64>,
+        reward_fee: u128,
+        treasury: String,
+        tier_policy: TierPolicy,
+        lock_boosts: Vec<LockBoost>,
+        tier_validity: Option<u64>,
     },
     UserInfo {
         tier: u8,
         timestamp: u64,
         usd_deposit: Uint128,
         orai_deposit: Uint128,
+        stable_deposits: Vec<Coin>,
+        auto_compound: bool,
+        downgrade_at: Option<u64>,
+        lock_end: u64,
+        effective_usd_deposit: Uint128,
+        expires_at: Option<u64>,
     },
     UserTotalDelegated {
         total_delegated: Uint128,
@@ -166,6 +430,132 @@ pub enum QueryResponse {
         amount: u32,
         unbonds: Vec<SerializedUnbonds>,
     },
+    CurrentEpoch {
+        epoch: SerializedEpoch,
+        batch_time: Option<u64>,
+        pending_batch: Option<u64>,
+    },
+    Epochs {
+        epochs: Vec<SerializedEpoch>,
+    },
+    UserEpochs {
+        epochs: Vec<SerializedUserEpoch>,
+    },
+    FundedDeposits {
+        deposits: Vec<SerializedFundedDeposit>,
+    },
+    Approval {
+        approval: Option<SerializedApproval>,
+    },
+    Approvals {
+        approvals: Vec<SerializedApproval>,
+    },
+    Hooks {
+        hooks: Vec<String>,
+    },
+    AllUsers {
+        users: Vec<SerializedUserInfo>,
+    },
+    TierStats {
+        tiers: Vec<SerializedTierStats>,
+    },
+    State {
+        validators: Vec<ValidatorState>,
+        total_delegated: Uint128,
+        queued: Uint128,
+        unbonding: Uint128,
+        claimable: Uint128,
+        exchange_rate: Option<Uint128>,
+        balance: Vec<Coin>,
+    },
+    Link {
+        linked: Option<String>,
+        proposed: Option<String>,
+        owner: Option<String>,
+        linked_at: Option<u64>,
+    },
+    PriceObservations {
+        twap_window: u64,
+        observations: Vec<SerializedPriceObservation>,
+    },
+    DelegationState {
+        total_shares: Uint128,
+        total_delegated: Uint128,
+        total_queued: Uint128,
+        reward_index: Uint128,
+    },
+    ValidatorsHealth {
+        validators: Vec<ValidatorHealth>,
+    },
+    Rewards {
+        pending_rewards: Uint128,
+    },
+    PreviewTier {
+        tier: u8,
+        usd_deposit: Uint128,
+        downgrade_at: Option<u64>,
+    },
+    SimulateDeposit {
+        tier: u8,
+        usd_value: Uint128,
+        usd_deposit: Uint128,
+        refund: Uint128,
+        next_tiers: Vec<TierRequirement>,
+    },
+    SimulateWithdraw {
+        amount: Uint128,
+        stable_deposits: Vec<Coin>,
+        tier: u8,
+        claim_time: u64,
+    },
+    Account {
+        tier: u8,
+        timestamp: u64,
+        usd_deposit: Uint128,
+        orai_deposit: Uint128,
+        stable_deposits: Vec<Coin>,
+        expires_at: Option<u64>,
+        lifetime_deposits: Vec<Coin>,
+        lifetime_usd_deposit: Uint128,
+        queued: Uint128,
+        unbonding: Vec<SerializedWithdrawals>,
+        claimable: Uint128,
+        pending_rewards: Uint128,
+    },
+    TierAt {
+        tier: u8,
+    },
+    TierHeld {
+        tier: u8,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ValidatorHealth {
+    pub address: String,
+    pub weight: u128,
+    pub active: bool,
+    pub delegated: Uint128,
+}
+
+/// What is still missing to reach `tier`.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TierRequirement {
+    pub tier: u8,
+    pub usd_amount: Uint128,
+    pub orai_amount: Uint128,
+}
+
+/// Actual delegation of a validator next to the amount its weight asks for.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ValidatorState {
+    pub address: String,
+    pub weight: u128,
+    pub target: Uint128,
+    pub delegated: Uint128,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -197,3 +587,69 @@ impl Clone for OraiswapContract {
         }
     }
 }
+
+/// Stable coin accepted as a deposit, `decimals` tells how many base units make 1 USD.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct StableDenom {
+    pub denom: String,
+    pub decimals: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceOracleSource {
+    Oraiswap {},
+    Band {
+        contract: String,
+    },
+    Fixed {
+        exchange_rate: Uint128,
+    },
+}
+
+/// Account actions an owner can approve an operator for. `Claim` covers `set_compounding`,
+/// `Refresh` covers `renew`. `Withdraw` also lets the operator open a deposit for the owner
+/// with `funder_withdraws`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Withdraw,
+    Claim,
+    Refresh,
+}
+
+/// Tier boost for deposits locked for at least `duration` seconds. `multiplier` is a
+/// percentage applied to the USD deposit, 150 counts the deposit one and a half times.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LockBoost {
+    pub duration: u64,
+    pub multiplier: u128,
+}
+
+/// How a refresh treats a deposit that is no longer worth its tier.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TierPolicy {
+    /// The tier is never lowered.
+    Sticky {},
+    /// The tier is lowered as soon as it is refreshed.
+    RefreshOnTouch {},
+    /// The tier is lowered by a refresh once the deposit stayed short for `seconds`.
+    GracePeriod {
+        seconds: u64,
+    },
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        TierPolicy::Sticky {}
+    }
+}
+
+impl Default for PriceOracleSource {
+    fn default() -> Self {
+        PriceOracleSource::Oraiswap {}
+    }
+}
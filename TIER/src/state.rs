@@ -1,20 +1,53 @@
+use crate::band::OraiPriceOracle;
 use crate::msg::{
     ContractStatus,
+    LockBoost,
     OraiswapContract,
+    PriceOracleSource,
+    Permission,
     QueryResponse,
+    SerializedApproval,
+    SerializedEpoch,
+    SerializedFundedDeposit,
+    SerializedPriceObservation,
+    SerializedTierStats,
     SerializedUnbonds,
+    SerializedUserInfo,
     SerializedWithdrawals,
+    StableDenom,
+    TierPolicy,
     ValidatorWithWeight,
 };
-use cosmwasm_std::{ StdError, StdResult, Storage, Uint128 };
+use cosmwasm_std::{ Coin, StdError, StdResult, Storage, Uint128 };
 use cw_storage_plus::{ Deque, Item, Map };
-use serde::{ Deserialize, Serialize };
+use serde::{ Deserialize, Deserializer, Serialize };
 
 pub const CONFIG_ITEM: Item<Config> = Item::new("config");
 pub const WITHDRAWALS_LIST: Map<String, Vec<UserWithdrawal>> = Map::new("withdraw"); //Deque<UserWithdrawal> = Deque::new("withdraw");
-pub const UNBOND_LIST: Deque<UserUnbond> = Deque::new("unbond_list");
+pub const UNBOND_EPOCH: Item<u64> = Item::new("unbond_epoch");
+pub const UNBOND_EPOCHS: Map<u64, UnbondEpoch> = Map::new("unbond_epochs");
+pub const EPOCH_UNBONDS: Map<(u64, String), UserUnbond> = Map::new("epoch_unbonds");
+pub const PENDING_BATCH: Item<PendingBatch> = Item::new("pending_batch");
 pub const USER_INFOS: Map<String, UserInfo> = Map::new("user_info");
 pub const USER_TOTAL_DELEGATED: Map<String, Uint128> = Map::new("user_total_delegate");
+pub const PRICE_OBSERVATIONS: Deque<PriceObservation> = Deque::new("price_observations");
+pub const PRICE_BASELINE: Item<PriceObservation> = Item::new("price_baseline");
+pub const DELEGATION_STATE: Item<DelegationState> = Item::new("delegation_state");
+pub const HOOKS: Item<Vec<String>> = Item::new("hooks");
+pub const APPROVALS: Map<(String, String), Approval> = Map::new("approvals");
+pub const LINK_PROPOSALS: Map<String, String> = Map::new("link_proposals");
+pub const LINKS: Map<String, String> = Map::new("links");
+pub const LINKED_OWNERS: Map<String, WalletLink> = Map::new("linked_owners");
+pub const FUNDED_DEPOSITS: Map<(String, String), FundedDeposit> = Map::new("funded_deposits");
+/// Funders of a beneficiary, to clear `FUNDED_DEPOSITS` once its deposit is gone.
+pub const DEPOSIT_FUNDERS: Map<(String, String), ()> = Map::new("deposit_funders");
+pub const LIFETIME_DEPOSITS: Map<String, LifetimeDeposits> = Map::new("lifetime_deposits");
+pub const TIER_HISTORY: Map<(String, u64), TierCheckpoint> = Map::new("tier_history");
+pub const TIER_HOLDERS: Map<(u8, String), ()> = Map::new("tier_holders");
+pub const TIER_STATS: Map<u8, TierStats> = Map::new("tier_stats");
+
+/// Precision of the per-share reward index.
+pub const REWARD_INDEX_SCALE: u128 = 1_000_000_000_000_000_000;
 
 // pub fn withdrawals_list(address: &CanonicalAddr) -> Deque<'static, UserWithdrawal> {
 //     WITHDRAWALS_LIST.push_back(address.as_slice())
@@ -27,7 +60,51 @@ pub struct Config {
     pub status: u8,
     pub usd_deposits: Vec<u128>,
     pub oraiswap_contract: OraiswapContract,
-    pub stable_denom: Vec<String>,
+    #[serde(deserialize_with = "deserialize_stable_denoms")]
+    pub stable_denom: Vec<StableDenom>,
+    #[serde(default)]
+    pub price_oracle: PriceOracleSource,
+    #[serde(default)]
+    pub twap_window: u64,
+    #[serde(default)]
+    pub max_price_deviation: Option<u128>,
+    #[serde(default)]
+    pub max_price_age: Option<u64>,
+    #[serde(default)]
+    pub reward_fee: u128,
+    #[serde(default)]
+    pub treasury: String,
+    #[serde(default)]
+    pub tier_policy: TierPolicy,
+    #[serde(default)]
+    pub lock_boosts: Vec<LockBoost>,
+    #[serde(default)]
+    pub tier_validity: Option<u64>,
+}
+
+/// Stable denominations stored before they had decimals were priced with 6.
+fn deserialize_stable_denoms<'de, D>(deserializer: D) -> Result<Vec<StableDenom>, D::Error>
+    where D: Deserializer<'de>
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredDenom {
+        Denom(String),
+        WithDecimals(StableDenom),
+    }
+
+    let denoms = Vec::<StoredDenom>::deserialize(deserializer)?;
+    Ok(
+        denoms
+            .into_iter()
+            .map(|denom| {
+                match denom {
+                    StoredDenom::Denom(denom) => StableDenom { denom, decimals: 6 },
+                    StoredDenom::WithDecimals(denom) => denom,
+                }
+            })
+            .collect()
+    )
 }
 
 impl Config {
@@ -61,6 +138,32 @@ impl Config {
             .unwrap() as u8
     }
 
+    /// Base units of the stable coin `denom` that are worth 1 USD.
+    pub fn stable_unit(&self, denom: &str) -> Option<u128> {
+        self.stable_denom
+            .iter()
+            .find(|s| s.denom == denom)
+            .map(|s| (10_u128).pow(s.decimals))
+    }
+
+    /// Best multiplier for a lock that still runs for `remaining` seconds, 100 without a boost.
+    pub fn lock_multiplier(&self, remaining: u64) -> u128 {
+        self.lock_boosts
+            .iter()
+            .filter(|b| b.duration <= remaining)
+            .map(|b| b.multiplier)
+            .max()
+            .unwrap_or(100)
+    }
+
+    /// Whether a tier last renewed at `renewed_at` is no longer valid at `now`.
+    pub fn tier_expired(&self, renewed_at: u64, now: u64) -> bool {
+        match self.tier_validity {
+            Some(tier_validity) => now >= renewed_at.saturating_add(tier_validity),
+            None => false,
+        }
+    }
+
     pub fn assert_contract_active(&self) -> StdResult<()> {
         let active = ContractStatus::Active as u8;
         if self.status != active {
@@ -85,6 +188,15 @@ impl Config {
                 .map(|d| Uint128::from(*d))
                 .collect(),
             stable_denom: self.stable_denom.clone(),
+            price_oracle: self.price_oracle.clone(),
+            twap_window: self.twap_window,
+            max_price_deviation: self.max_price_deviation,
+            max_price_age: self.max_price_age,
+            reward_fee: self.reward_fee,
+            treasury: self.treasury.clone(),
+            tier_policy: self.tier_policy.clone(),
+            lock_boosts: self.lock_boosts.clone(),
+            tier_validity: self.tier_validity,
         });
     }
 }
@@ -96,15 +208,185 @@ pub struct UserInfo {
     pub usd_deposit: u128,
     pub orai_deposit: u128,
     pub total_orai_deposit: u128,
+    #[serde(default)]
+    pub stable_deposits: Vec<Coin>,
+    #[serde(default)]
+    pub shares: u128,
+    #[serde(default)]
+    pub reward_index: u128,
+    #[serde(default)]
+    pub pending_rewards: u128,
+    #[serde(default)]
+    pub auto_compound: bool,
+    #[serde(default)]
+    pub downgrade_at: Option<u64>,
+    #[serde(default)]
+    pub lock_end: u64,
+    #[serde(default)]
+    pub lock_multiplier: u128,
+    #[serde(default)]
+    pub renewed_at: u64,
+    #[serde(default)]
+    pub withdrawer: Option<String>,
 }
 
 impl UserInfo {
-    pub fn to_answer(&self) -> QueryResponse {
+    pub fn to_answer(&self, config: &Config, now: u64) -> QueryResponse {
         QueryResponse::UserInfo {
-            tier: self.tier,
+            tier: self.effective_tier(config, now),
             timestamp: self.timestamp,
             usd_deposit: Uint128::from(self.usd_deposit),
             orai_deposit: Uint128::from(self.orai_deposit),
+            stable_deposits: self.stable_deposits.clone(),
+            auto_compound: self.auto_compound,
+            downgrade_at: self.downgrade_at,
+            lock_end: self.lock_end,
+            effective_usd_deposit: Uint128::from(self.effective_usd_deposit(now)),
+            expires_at: config.tier_validity.map(|v| self.renewed_at.saturating_add(v)),
+        }
+    }
+
+    /// USD value of the stable coins, every denomination with its own decimals.
+    pub fn stable_usd_deposit(&self, config: &Config) -> u128 {
+        self.stable_deposits
+            .iter()
+            .map(|c| {
+                let unit = config.stable_unit(&c.denom).unwrap_or(OraiPriceOracle::ZERO_6);
+                c.amount.multiply_ratio(OraiPriceOracle::ZERO_6, unit).u128()
+            })
+            .sum::<u128>()
+            .checked_div(OraiPriceOracle::ZERO_6)
+            .unwrap()
+    }
+
+    /// Tier reported to others, the minimal one once the tier was not renewed in time.
+    pub fn effective_tier(&self, config: &Config, now: u64) -> u8 {
+        if config.tier_expired(self.renewed_at, now) {
+            config.min_tier()
+        } else {
+            self.current_tier(config, now)
+        }
+    }
+
+    /// Tier without the lock boost. A lower stored tier is kept, tier policies delay downgrades.
+    pub fn base_tier(&self, config: &Config) -> u8 {
+        self.tier.max(config.tier_by_deposit(self.usd_deposit))
+    }
+
+    /// Tier held at `now`, a tier reached with the lock boost ends with the lock.
+    pub fn current_tier(&self, config: &Config, now: u64) -> u8 {
+        if self.lock_end > now { self.tier } else { self.base_tier(config) }
+    }
+
+    /// Drops the tier reached with the lock boost once the lock is over.
+    pub fn end_lock(&mut self, config: &Config, now: u64) {
+        let tier = self.current_tier(config, now);
+        if tier != self.tier {
+            self.tier = tier;
+            self.timestamp = now;
+            self.downgrade_at = None;
+        }
+    }
+
+    /// Multiplier in percent of the running lock.
+    pub fn boost(&self, now: u64) -> u128 {
+        if self.lock_end > now { self.lock_multiplier } else { 100 }
+    }
+
+    /// USD deposit with the lock boost applied, this is what the tier is based on.
+    pub fn effective_usd_deposit(&self, now: u64) -> u128 {
+        Uint128::from(self.usd_deposit).multiply_ratio(self.boost(now), 100_u128).u128()
+    }
+
+    /// USD deposit needed for an effective value of `effective_usd` with the running lock.
+    pub fn required_usd_deposit(&self, effective_usd: u128, now: u64) -> u128 {
+        let boost = self.boost(now);
+        effective_usd.checked_mul(100).unwrap().checked_add(boost - 1).unwrap() / boost
+    }
+
+    /// Locks the whole deposit for at least `duration` seconds. The multiplier follows the
+    /// time that is left on the lock.
+    pub fn lock(&mut self, config: &Config, duration: u64, now: u64) -> StdResult<()> {
+        if !config.lock_boosts.iter().any(|b| b.duration == duration) {
+            return Err(StdError::generic_err("Unsupported lock duration"));
+        }
+
+        self.lock_end = self.lock_end.max(now.checked_add(duration).unwrap());
+        self.lock_multiplier = config.lock_multiplier(self.lock_end - now);
+        Ok(())
+    }
+
+    /// Re-evaluates the tier for a deposit that is worth `usd_deposit` now. Upgrades apply
+    /// right away, downgrades follow the tier policy of the config.
+    pub fn reprice(&mut self, config: &Config, usd_deposit: u128, now: u64) {
+        let tier = config.tier_by_deposit(
+            Uint128::from(usd_deposit).multiply_ratio(self.boost(now), 100_u128).u128()
+        );
+        if tier <= self.tier {
+            if tier < self.tier {
+                self.tier = tier;
+                self.timestamp = now;
+            }
+            self.usd_deposit = usd_deposit;
+            self.downgrade_at = None;
+            return;
+        }
+
+        let downgrade_at = match config.tier_policy {
+            TierPolicy::Sticky {} => {
+                return;
+            }
+            TierPolicy::RefreshOnTouch {} => now,
+            TierPolicy::GracePeriod { seconds } => {
+                *self.downgrade_at.get_or_insert(now.checked_add(seconds).unwrap())
+            }
+        };
+
+        if now >= downgrade_at {
+            self.tier = tier;
+            self.usd_deposit = usd_deposit;
+            self.downgrade_at = None;
+        } else {
+            self.downgrade_at = Some(downgrade_at);
+        }
+    }
+
+    /// Moves the rewards earned by the current shares since the last settlement into
+    /// `pending_rewards`. Has to be called before the shares change.
+    pub fn settle_rewards(&mut self, reward_index: u128) {
+        let index_delta = reward_index.checked_sub(self.reward_index).unwrap();
+        let earned = Uint128::from(self.shares)
+            .multiply_ratio(index_delta, REWARD_INDEX_SCALE)
+            .u128();
+        self.pending_rewards = self.pending_rewards.checked_add(earned).unwrap();
+        self.reward_index = reward_index;
+    }
+
+    pub fn checkpoint(&self, config: &Config) -> TierCheckpoint {
+        TierCheckpoint {
+            tier: self.tier,
+            renewed_at: self.renewed_at,
+            base_tier: self.base_tier(config),
+            lock_end: self.lock_end,
+        }
+    }
+
+    pub fn add_stable_deposit(&mut self, denom: &str, amount: u128) {
+        add_coin(&mut self.stable_deposits, denom, amount);
+    }
+
+    /// Address that may withdraw the deposit of `address`.
+    pub fn withdrawer_or(&self, address: &str) -> String {
+        self.withdrawer.clone().unwrap_or(address.to_string())
+    }
+
+    pub fn to_serialized(&self, address: String, config: &Config, now: u64) -> SerializedUserInfo {
+        SerializedUserInfo {
+            address,
+            tier: self.effective_tier(config, now),
+            timestamp: self.timestamp,
+            usd_deposit: Uint128::new(self.usd_deposit),
+            orai_deposit: Uint128::new(self.orai_deposit),
         }
     }
 }
@@ -112,20 +394,56 @@ impl UserInfo {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct UserWithdrawal {
     pub amount: u128,
-    pub claim_time: u64,
     pub timestamp: u64,
+    #[serde(default)]
+    pub epoch_id: u64,
 }
 
 impl UserWithdrawal {
-    pub fn to_serialized(&self) -> SerializedWithdrawals {
+    pub fn to_serialized(&self, claim_time: u64) -> SerializedWithdrawals {
         SerializedWithdrawals {
             amount: Uint128::from(self.amount),
-            claim_time: self.claim_time,
+            claim_time,
             timestamp: self.timestamp,
+            epoch_id: self.epoch_id,
+        }
+    }
+}
+
+/// Withdrawals unbonded together. The epoch is open until `BatchUnbond` closes it,
+/// its withdrawals are claimable from `claim_time`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnbondEpoch {
+    pub amount: u128,
+    pub start_time: u64,
+    pub close_time: Option<u64>,
+    pub claim_time: Option<u64>,
+    pub claimed: u128,
+}
+
+impl UnbondEpoch {
+    pub fn unclaimed(&self) -> u128 {
+        self.amount.saturating_sub(self.claimed)
+    }
+
+    pub fn to_serialized(&self, id: u64) -> SerializedEpoch {
+        SerializedEpoch {
+            id,
+            amount: Uint128::from(self.amount),
+            start_time: self.start_time,
+            close_time: self.close_time,
+            claim_time: self.claim_time,
         }
     }
 }
 
+/// Closed epoch whose entries are still being processed, `last_address` is the cursor.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PendingBatch {
+    pub epoch_id: u64,
+    pub last_address: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct UserUnbond {
     pub address: String,
@@ -142,3 +460,228 @@ impl UserUnbond {
         }
     }
 }
+
+fn add_coin(coins: &mut Vec<Coin>, denom: &str, amount: u128) {
+    match coins.iter_mut().find(|c| c.denom == denom) {
+        Some(coin) => {
+            coin.amount = coin.amount.checked_add(Uint128::from(amount)).unwrap();
+        }
+        None => coins.push(Coin::new(amount, denom)),
+    }
+}
+
+/// Actions an operator may take for the owner of an account.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Approval {
+    pub permissions: Vec<Permission>,
+    pub expires: Option<u64>,
+}
+
+impl Approval {
+    pub fn allows(&self, permission: &Permission, now: u64) -> bool {
+        let expired = self.expires.is_some_and(|expires| now >= expires);
+        !expired && self.permissions.contains(permission)
+    }
+
+    pub fn to_serialized(&self, operator: String) -> SerializedApproval {
+        SerializedApproval {
+            operator,
+            permissions: self.permissions.clone(),
+            expires: self.expires,
+        }
+    }
+}
+
+/// The tier owner a linked address inherits its tier from.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WalletLink {
+    pub owner: String,
+    pub linked_at: u64,
+}
+
+/// Everything a funder deposited for one beneficiary.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FundedDeposit {
+    pub deposits: Vec<Coin>,
+    pub usd_deposit: u128,
+    pub funder_withdraws: bool,
+    pub timestamp: u64,
+}
+
+impl FundedDeposit {
+    pub fn add(
+        &mut self,
+        denom: &str,
+        amount: u128,
+        usd_amount: u128,
+        funder_withdraws: bool,
+        timestamp: u64
+    ) {
+        add_coin(&mut self.deposits, denom, amount);
+        self.usd_deposit = self.usd_deposit.checked_add(usd_amount).unwrap();
+        self.funder_withdraws = funder_withdraws;
+        self.timestamp = timestamp;
+    }
+
+    pub fn to_serialized(&self, beneficiary: String) -> SerializedFundedDeposit {
+        SerializedFundedDeposit {
+            beneficiary,
+            deposits: self.deposits.clone(),
+            usd_deposit: Uint128::from(self.usd_deposit),
+            funder_withdraws: self.funder_withdraws,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Everything ever deposited for an address, kept after its position is closed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LifetimeDeposits {
+    pub deposits: Vec<Coin>,
+    pub usd_deposit: u128,
+}
+
+impl LifetimeDeposits {
+    pub fn add(&mut self, denom: &str, amount: u128, usd_amount: u128) {
+        add_coin(&mut self.deposits, denom, amount);
+        self.usd_deposit = self.usd_deposit.checked_add(usd_amount).unwrap();
+    }
+}
+
+/// Running totals of the positions stored with one tier.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TierStats {
+    pub holders: u64,
+    pub usd_deposit: u128,
+    pub orai_deposit: u128,
+}
+
+impl TierStats {
+    pub fn add(&mut self, user_info: &UserInfo) {
+        self.holders = self.holders.checked_add(1).unwrap();
+        self.usd_deposit = self.usd_deposit.checked_add(user_info.usd_deposit).unwrap();
+        self.orai_deposit = self.orai_deposit.checked_add(user_info.orai_deposit).unwrap();
+    }
+
+    pub fn sub(&mut self, user_info: &UserInfo) {
+        self.holders = self.holders.saturating_sub(1);
+        self.usd_deposit = self.usd_deposit.saturating_sub(user_info.usd_deposit);
+        self.orai_deposit = self.orai_deposit.saturating_sub(user_info.orai_deposit);
+    }
+
+    pub fn to_serialized(&self, tier: u8) -> SerializedTierStats {
+        SerializedTierStats {
+            tier,
+            holders: self.holders,
+            usd_deposit: Uint128::new(self.usd_deposit),
+            orai_deposit: Uint128::new(self.orai_deposit),
+        }
+    }
+}
+
+/// Tier of an address from the time the checkpoint was written.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TierCheckpoint {
+    pub tier: u8,
+    pub renewed_at: u64,
+    /// Tier without the lock boost, held from `lock_end` on
+    #[serde(default)]
+    pub base_tier: u8,
+    #[serde(default)]
+    pub lock_end: u64,
+}
+
+impl TierCheckpoint {
+    pub fn minimal(config: &Config) -> Self {
+        TierCheckpoint {
+            tier: config.min_tier(),
+            renewed_at: 0,
+            base_tier: config.min_tier(),
+            lock_end: 0,
+        }
+    }
+
+    pub fn tier_at(&self, timestamp: u64) -> u8 {
+        if timestamp < self.lock_end { self.tier } else { self.tier.max(self.base_tier) }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PriceObservation {
+    pub exchange_rate: u128,
+    pub timestamp: u64,
+}
+
+impl PriceObservation {
+    pub fn to_serialized(&self) -> SerializedPriceObservation {
+        SerializedPriceObservation {
+            exchange_rate: Uint128::from(self.exchange_rate),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Share accounting of the delegated ORAI. `total_delegated` belongs to the share holders,
+/// `total_queued` was already withdrawn by users but is not undelegated yet. `reward_index`
+/// is the staking reward paid per share so far, scaled by `REWARD_INDEX_SCALE`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DelegationState {
+    pub total_shares: u128,
+    pub total_delegated: u128,
+    pub total_queued: u128,
+    pub reward_index: u128,
+}
+
+impl DelegationState {
+    pub fn shares_for(&self, amount: u128) -> u128 {
+        if self.total_shares == 0 || self.total_delegated == 0 {
+            return amount;
+        }
+
+        Uint128::from(amount).multiply_ratio(self.total_shares, self.total_delegated).u128()
+    }
+
+    pub fn amount_for(&self, shares: u128) -> u128 {
+        if self.total_shares == 0 {
+            return 0;
+        }
+
+        Uint128::from(shares).multiply_ratio(self.total_delegated, self.total_shares).u128()
+    }
+
+    pub fn mint(&mut self, amount: u128) -> u128 {
+        let shares = self.shares_for(amount);
+        self.total_shares = self.total_shares.checked_add(shares).unwrap();
+        self.total_delegated = self.total_delegated.checked_add(amount).unwrap();
+        shares
+    }
+
+    pub fn burn(&mut self, shares: u128) -> u128 {
+        let amount = self.amount_for(shares);
+        self.total_shares = self.total_shares.checked_sub(shares).unwrap();
+        self.total_delegated = self.total_delegated.checked_sub(amount).unwrap();
+        self.total_queued = self.total_queued.checked_add(amount).unwrap();
+        amount
+    }
+
+    /// Spreads harvested rewards over the current shares.
+    pub fn distribute_rewards(&mut self, amount: u128) {
+        if self.total_shares == 0 {
+            return;
+        }
+
+        let increase = Uint128::from(amount)
+            .multiply_ratio(REWARD_INDEX_SCALE, self.total_shares)
+            .u128();
+        self.reward_index = self.reward_index.checked_add(increase).unwrap();
+    }
+
+    pub fn to_answer(&self) -> QueryResponse {
+        QueryResponse::DelegationState {
+            total_shares: Uint128::from(self.total_shares),
+            total_delegated: Uint128::from(self.total_delegated),
+            total_queued: Uint128::from(self.total_queued),
+            reward_index: Uint128::from(self.reward_index),
+        }
+    }
+}